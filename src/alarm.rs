@@ -0,0 +1,349 @@
+use crate::event_log::LogEntry;
+use crate::lin2db;
+use crate::measurement::{MeasurementFrame, MAX_CHANNELS};
+use std::fmt;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// A value of a frame an alarm rule can watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmMetric {
+    /// The sample peak in dBFS
+    Peak,
+    /// The true peak in dBTP
+    TruePeak,
+    /// The RMS in dBFS
+    Rms,
+    /// The momentary loudness in LUFS
+    Momentary,
+    /// The short-term loudness in LUFS
+    ShortTerm,
+    /// The integrated loudness in LUFS
+    Integrated,
+    /// The phase correlation of the first two channels in \[-1,1\]
+    Correlation,
+}
+
+impl AlarmMetric {
+    const ALL: [AlarmMetric; 7] = [
+        AlarmMetric::Peak,
+        AlarmMetric::TruePeak,
+        AlarmMetric::Rms,
+        AlarmMetric::Momentary,
+        AlarmMetric::ShortTerm,
+        AlarmMetric::Integrated,
+        AlarmMetric::Correlation,
+    ];
+
+    /// The name used in the rules
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlarmMetric::Peak => "peak",
+            AlarmMetric::TruePeak => "true_peak",
+            AlarmMetric::Rms => "rms",
+            AlarmMetric::Momentary => "momentary",
+            AlarmMetric::ShortTerm => "short_term",
+            AlarmMetric::Integrated => "integrated",
+            AlarmMetric::Correlation => "correlation",
+        }
+    }
+
+    /// Whether the metric has a value per channel, or one for the pair
+    fn per_channel(&self) -> bool {
+        *self != AlarmMetric::Correlation
+    }
+
+    /// The value of the metric for the channel of the frame
+    fn value(&self, frame: &MeasurementFrame, channel: usize) -> f32 {
+        let measurement = &frame.channels[channel];
+        match self {
+            AlarmMetric::Peak => lin2db(measurement.peak),
+            AlarmMetric::TruePeak => lin2db(measurement.true_peak),
+            AlarmMetric::Rms => lin2db(measurement.rms),
+            AlarmMetric::Momentary => measurement.momentary,
+            AlarmMetric::ShortTerm => measurement.short_term,
+            AlarmMetric::Integrated => measurement.integrated,
+            AlarmMetric::Correlation => frame.correlation,
+        }
+    }
+}
+
+/// How the value is compared to the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+/// A condition that raises an alarm once it held for a duration.
+///
+/// Rules are written as `<metric> <op> <threshold> [for <seconds>]`, like `true_peak > -1`,
+/// `short_term > -18 for 10` or `correlation < -0.3`. The operator is `>` or `<`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlarmRule {
+    pub metric: AlarmMetric,
+    pub comparison: Comparison,
+    pub threshold: f32,
+    /// The time the condition has to hold before the alarm is raised in seconds
+    pub duration: f32,
+}
+
+impl AlarmRule {
+    /// Parse a rule, or return `None` if it isn't valid
+    pub fn parse(rule: &str) -> Option<Self> {
+        let words: Vec<&str> = rule.split_whitespace().collect();
+
+        let duration = match words.len() {
+            3 => 0.0,
+            5 if words[3] == "for" => words[4].parse::<f32>().ok().filter(|d| *d >= 0.0)?,
+            _ => return None,
+        };
+
+        Some(Self {
+            metric: *AlarmMetric::ALL
+                .iter()
+                .find(|metric| metric.name() == words[0])?,
+            comparison: match words[1] {
+                ">" => Comparison::Above,
+                "<" => Comparison::Below,
+                _ => return None,
+            },
+            threshold: words[2].parse().ok()?,
+            duration,
+        })
+    }
+
+    /// Whether the value meets the condition
+    fn matches(&self, value: f32) -> bool {
+        match self.comparison {
+            Comparison::Above => value > self.threshold,
+            Comparison::Below => value < self.threshold,
+        }
+    }
+}
+
+impl fmt::Display for AlarmRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.comparison {
+            Comparison::Above => ">",
+            Comparison::Below => "<",
+        };
+        write!(f, "{} {} {}", self.metric.name(), op, self.threshold)?;
+        if self.duration > 0.0 {
+            write!(f, " for {} s", self.duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a rule on a single channel
+#[derive(Debug, Clone, Copy, Default)]
+struct AlarmState {
+    /// The position of the first sample of the frame since which the condition holds
+    since: Option<u64>,
+    active: bool,
+}
+
+/// Evaluates the alarm rules on every frame, per channel.
+///
+/// An alarm is raised once its condition held for the duration of the rule,
+/// and cleared with the first frame in which it doesn't hold anymore.
+/// Rules on the correlation are evaluated once for the pair of channels.
+/// The rules always watch the L/R inputs, also while the meters show Mid/Side.
+pub struct AlarmEngine {
+    rules: Vec<AlarmRule>,
+    sample_rate: f32,
+    states: Vec<[AlarmState; MAX_CHANNELS]>,
+}
+
+impl AlarmEngine {
+    pub fn new(rules: Vec<AlarmRule>, sample_rate: f32) -> Self {
+        Self {
+            states: vec![[AlarmState::default(); MAX_CHANNELS]; rules.len()],
+            rules,
+            sample_rate,
+        }
+    }
+
+    /// Add a frame and collect a log entry for every alarm that was raised or cleared
+    pub fn update(&mut self, frame: &MeasurementFrame, entries: &mut Vec<LogEntry>) {
+        let frame = frame.stereo_frame();
+        let end = frame.position + frame.length as u64;
+
+        for (rule, states) in self.rules.iter().zip(&mut self.states) {
+            let channels = if rule.metric.per_channel() {
+                frame.channel_count
+            } else {
+                1
+            };
+            let duration = (rule.duration * self.sample_rate) as u64;
+
+            for (channel, state) in states[..channels].iter_mut().enumerate() {
+                let value = rule.metric.value(&frame, channel);
+
+                let active = if rule.matches(value) {
+                    let since = *state.since.get_or_insert(frame.position);
+                    end - since >= duration
+                } else {
+                    state.since = None;
+                    false
+                };
+
+                if active == state.active {
+                    continue;
+                }
+                state.active = active;
+
+                let subject = if rule.metric.per_channel() {
+                    format!("channel {}", channel + 1)
+                } else {
+                    String::from("pair")
+                };
+                let change = if active { "raised" } else { "cleared" };
+                entries.push(LogEntry::new(
+                    frame.frame_time,
+                    format!(
+                        "alarm {}: {} {} (value {:.2})",
+                        change, subject, rule, value
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Evaluate the rules on the frames from the receiver and send the changes to the event log
+pub fn spawn_engine(
+    rules: Vec<AlarmRule>,
+    sample_rate: f32,
+    frames: Receiver<MeasurementFrame>,
+    log: Sender<LogEntry>,
+) -> JoinHandle<()> {
+    let mut engine = AlarmEngine::new(rules, sample_rate);
+
+    thread::spawn(move || {
+        let mut entries = Vec::new();
+        while let Ok(frame) = frames.recv() {
+            engine.update(&frame, &mut entries);
+            // The engine keeps running without the log, the entries are only reported
+            for entry in entries.drain(..) {
+                let _ = log.send(entry);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+    const FRAME_LENGTH: u32 = 100;
+
+    /// A frame with the true peak of the left and right input in dBTP
+    fn frame(index: u64, left: f32, right: f32) -> MeasurementFrame {
+        let mut frame = MeasurementFrame::new(index * FRAME_LENGTH as u64, FRAME_LENGTH, 2);
+        frame.stereo[0].true_peak = crate::db2lin(left);
+        frame.stereo[1].true_peak = crate::db2lin(right);
+        frame.channels = frame.stereo_frame().channels;
+        frame
+    }
+
+    /// Add the frames and return the messages of the log entries
+    fn update(engine: &mut AlarmEngine, frames: &[MeasurementFrame]) -> Vec<String> {
+        let mut entries = Vec::new();
+        for frame in frames {
+            engine.update(frame, &mut entries);
+        }
+        entries.into_iter().map(|entry| entry.message).collect()
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(
+            AlarmRule::parse("short_term > -18 for 10"),
+            Some(AlarmRule {
+                metric: AlarmMetric::ShortTerm,
+                comparison: Comparison::Above,
+                threshold: -18.0,
+                duration: 10.0,
+            })
+        );
+        assert_eq!(
+            AlarmRule::parse("  correlation <  -0.3 "),
+            Some(AlarmRule {
+                metric: AlarmMetric::Correlation,
+                comparison: Comparison::Below,
+                threshold: -0.3,
+                duration: 0.0,
+            })
+        );
+        assert_eq!(
+            AlarmRule::parse("true_peak > -1").map(|rule| rule.to_string()),
+            Some("true_peak > -1".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in [
+            "",
+            "loudness > -18",
+            "peak >= -1",
+            "peak > loud",
+            "peak > -1 for",
+            "peak > -1 during 10",
+            "peak > -1 for -5",
+            "peak > -1 for 10 s",
+        ] {
+            assert_eq!(AlarmRule::parse(rule), None, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn raised_after_the_hold_time_and_cleared_at_once() {
+        let rule = AlarmRule::parse("true_peak > -1 for 1").unwrap();
+        let mut engine = AlarmEngine::new(vec![rule], SAMPLE_RATE);
+
+        let over: Vec<_> = (0..9).map(|index| frame(index, 0.0, -6.0)).collect();
+        assert!(update(&mut engine, &over).is_empty());
+
+        // The tenth frame over the threshold completes the second
+        let messages = update(&mut engine, &[frame(9, 0.0, -6.0), frame(10, 0.0, -6.0)]);
+        assert_eq!(
+            messages,
+            vec!["alarm raised: channel 1 true_peak > -1 for 1 s (value 0.00)"]
+        );
+
+        let messages = update(&mut engine, &[frame(11, -6.0, -6.0)]);
+        assert_eq!(
+            messages,
+            vec!["alarm cleared: channel 1 true_peak > -1 for 1 s (value -6.00)"]
+        );
+    }
+
+    #[test]
+    fn a_break_restarts_the_hold_time() {
+        let rule = AlarmRule::parse("true_peak > -1 for 1").unwrap();
+        let mut engine = AlarmEngine::new(vec![rule], SAMPLE_RATE);
+
+        let mut frames: Vec<_> = (0..5).map(|index| frame(index, 0.0, -6.0)).collect();
+        frames.push(frame(5, -6.0, -6.0));
+        frames.extend((6..15).map(|index| frame(index, 0.0, -6.0)));
+
+        assert!(update(&mut engine, &frames).is_empty());
+    }
+
+    #[test]
+    fn rules_watch_the_inputs_in_mid_side_mode() {
+        let rule = AlarmRule::parse("true_peak > -1").unwrap();
+        let mut engine = AlarmEngine::new(vec![rule], SAMPLE_RATE);
+
+        // The mid of two inputs at -2 dBTP is louder than either of them
+        let mut frame = frame(0, -2.0, -2.0);
+        frame.channels[0].true_peak = crate::db2lin(0.0);
+        frame.channels[1].true_peak = 0.0;
+
+        assert!(update(&mut engine, &[frame]).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+/// A single second order IIR section in transposed direct form II.
+///
+/// The coefficients are normalised so that `a0` is always 1.0.
+/// The state is kept in `f64` since the low band filters of the analyser
+/// have poles very close to the unit circle.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Filter a single sample
+    pub fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    /// Clear the filter state without touching the coefficients
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    /// The magnitude response of the section at the normalised angular frequency `w` in [0, pi]
+    pub fn magnitude(&self, w: f64) -> f64 {
+        // Evaluate H(e^jw) = (b0 + b1 e^-jw + b2 e^-2jw) / (1 + a1 e^-jw + a2 e^-2jw)
+        let (c1, s1) = (w.cos(), -w.sin());
+        let (c2, s2) = ((2.0 * w).cos(), -(2.0 * w).sin());
+
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = self.b1 * s1 + self.b2 * s2;
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = self.a1 * s1 + self.a2 * s2;
+
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+
+    /// Scale the numerator so the whole section gets multiplied by `gain`
+    pub fn scale(&mut self, gain: f64) {
+        self.b0 *= gain;
+        self.b1 *= gain;
+        self.b2 *= gain;
+    }
+}
+
+/// Multiple biquads in series
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    sections: Vec<Biquad>,
+}
+
+impl Cascade {
+    pub fn new(sections: Vec<Biquad>) -> Self {
+        Self { sections }
+    }
+
+    pub fn process(&mut self, x: f64) -> f64 {
+        self.sections
+            .iter_mut()
+            .fold(x, |acc, section| section.process(acc))
+    }
+
+    pub fn reset(&mut self) {
+        for section in self.sections.iter_mut() {
+            section.reset();
+        }
+    }
+
+    /// The magnitude response of the whole cascade at the normalised angular frequency `w` in [0, pi]
+    pub fn magnitude(&self, w: f64) -> f64 {
+        self.sections
+            .iter()
+            .map(|section| section.magnitude(w))
+            .product()
+    }
+}
@@ -0,0 +1,386 @@
+use crate::create_timestamped;
+use crate::format_timestamp;
+use crate::lin2db;
+use crate::loudness::{
+    lufs_to_mean_square, mean_square_to_lufs, IntegratedLoudness, LoudnessRange,
+};
+use crate::measurement::{ChannelMeasurement, MeasurementFrame};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A loudness target a programme is checked against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessProfile {
+    pub name: &'static str,
+    /// The name used on the command line
+    pub key: &'static str,
+    /// The target integrated loudness in LUFS
+    pub target: f32,
+    /// The allowed deviation from the target in LU
+    pub tolerance: f32,
+    /// The highest allowed true peak in dBTP
+    pub max_true_peak: f32,
+}
+
+/// The profiles that can be selected.
+/// The streaming services normalise to their target, so a deviation of 1 LU is accepted there.
+pub const PROFILES: [LoudnessProfile; 5] = [
+    LoudnessProfile {
+        name: "EBU R128",
+        key: "ebu",
+        target: -23.0,
+        tolerance: 0.5,
+        max_true_peak: -1.0,
+    },
+    LoudnessProfile {
+        name: "ATSC A/85",
+        key: "atsc",
+        target: -24.0,
+        tolerance: 2.0,
+        max_true_peak: -2.0,
+    },
+    LoudnessProfile {
+        name: "Spotify",
+        key: "spotify",
+        target: -14.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+    },
+    LoudnessProfile {
+        name: "Apple Music",
+        key: "apple",
+        target: -16.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+    },
+    LoudnessProfile {
+        name: "YouTube",
+        key: "youtube",
+        target: -14.0,
+        tolerance: 1.0,
+        max_true_peak: -1.0,
+    },
+];
+
+impl LoudnessProfile {
+    /// Find a profile by the name used on the command line
+    pub fn from_key(key: &str) -> Option<Self> {
+        PROFILES.iter().find(|profile| profile.key == key).copied()
+    }
+
+    /// The profile after this one, wrapping around
+    pub fn next(&self) -> Self {
+        let index = PROFILES
+            .iter()
+            .position(|profile| profile == self)
+            .unwrap_or(0);
+        PROFILES[(index + 1) % PROFILES.len()]
+    }
+}
+
+/// Collects the loudness of the programme over a session for the compliance report.
+///
+/// The channels are summed into a programme loudness following ITU-R BS.1770, by adding up
+/// the mean squares behind the momentary and short-term loudness of every channel.
+/// These are taken every 100ms, which makes the momentary values the gating blocks of the
+/// integrated loudness and the short-term values the input of the loudness range.
+/// The left and right input are used in the Mid/Side mode too, since the loudness of
+/// the programme is defined on them.
+pub struct LoudnessSession {
+    sample_rate: f32,
+    integrated: IntegratedLoudness,
+    range: LoudnessRange,
+    max_true_peak: f32,
+    max_momentary: f32,
+    max_short_term: f32,
+    /// The amount of samples collected
+    length: u64,
+    /// The samples left until the next 100ms block is taken
+    block_countdown: u64,
+}
+
+impl LoudnessSession {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            integrated: IntegratedLoudness::new(),
+            range: LoudnessRange::new(),
+            max_true_peak: 0.0,
+            max_momentary: f32::NEG_INFINITY,
+            max_short_term: f32::NEG_INFINITY,
+            length: 0,
+            block_countdown: Self::block_length(sample_rate),
+        }
+    }
+
+    fn block_length(sample_rate: f32) -> u64 {
+        (sample_rate / 10.0).round() as u64
+    }
+
+    /// Add the frames of the session
+    pub fn add_frames(&mut self, frames: &[MeasurementFrame]) {
+        for frame in frames.iter().map(MeasurementFrame::stereo_frame) {
+            for channel in frame.channels() {
+                self.max_true_peak = self.max_true_peak.max(channel.true_peak);
+            }
+
+            self.length += frame.length as u64;
+
+            // The loudness only changes once per block, so it is taken in the first frame after it
+            let length = frame.length as u64;
+            if length < self.block_countdown {
+                self.block_countdown -= length;
+                continue;
+            }
+            let block_length = Self::block_length(self.sample_rate);
+            self.block_countdown = block_length - (length - self.block_countdown) % block_length;
+
+            let programme = |loudness: fn(&ChannelMeasurement) -> f32| -> f64 {
+                frame
+                    .channels()
+                    .iter()
+                    .map(|channel| lufs_to_mean_square(loudness(channel)))
+                    .sum()
+            };
+
+            let momentary = programme(|channel| channel.momentary);
+            let short_term = mean_square_to_lufs(programme(|channel| channel.short_term));
+
+            self.integrated.add_block(momentary);
+            self.range.add(short_term);
+            self.max_momentary = self.max_momentary.max(mean_square_to_lufs(momentary));
+            self.max_short_term = self.max_short_term.max(short_term);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+
+    /// Summarise the session and check it against the profile
+    pub fn report(&self, profile: LoudnessProfile) -> ComplianceReport {
+        let integrated = self.integrated.loudness();
+        let max_true_peak = lin2db(self.max_true_peak);
+
+        ComplianceReport {
+            profile,
+            created: SystemTime::now(),
+            duration: self.length as f32 / self.sample_rate,
+            integrated,
+            range: self.range.range(),
+            max_true_peak,
+            max_momentary: self.max_momentary,
+            max_short_term: self.max_short_term,
+            loudness_passed: (integrated - profile.target).abs() <= profile.tolerance,
+            true_peak_passed: max_true_peak <= profile.max_true_peak,
+        }
+    }
+}
+
+/// The loudness of a session checked against a profile
+#[derive(Debug, Clone)]
+pub struct ComplianceReport {
+    pub profile: LoudnessProfile,
+    pub created: SystemTime,
+    /// The length of the session in seconds
+    pub duration: f32,
+    /// The integrated loudness in LUFS
+    pub integrated: f32,
+    /// The loudness range in LU, if enough was measured for it
+    pub range: Option<f32>,
+    /// The highest true peak in dBTP
+    pub max_true_peak: f32,
+    /// The highest momentary loudness in LUFS
+    pub max_momentary: f32,
+    /// The highest short-term loudness in LUFS
+    pub max_short_term: f32,
+    /// Whether the integrated loudness is within the tolerance of the target
+    pub loudness_passed: bool,
+    /// Whether the true peak stayed at or below the limit
+    pub true_peak_passed: bool,
+}
+
+impl ComplianceReport {
+    pub fn passed(&self) -> bool {
+        self.loudness_passed && self.true_peak_passed
+    }
+
+    /// The report as readable text
+    pub fn to_text(&self) -> String {
+        let verdict = |passed: bool| if passed { "PASS" } else { "FAIL" };
+        let level = |value: f32| {
+            if value.is_finite() {
+                format!("{:.1}", value)
+            } else {
+                String::from("-inf")
+            }
+        };
+        let seconds = self.duration as u64;
+
+        format!(
+            concat!(
+                "Loudness compliance report\n",
+                "Created: {}\n",
+                "Profile: {} ({:.1} LUFS +/- {:.1} LU, true peak at most {:.1} dBTP)\n",
+                "Duration: {:02}:{:02}:{:02}\n",
+                "\n",
+                "Integrated loudness: {} LUFS  {}\n",
+                "Loudness range: {} LU\n",
+                "Max true peak: {} dBTP  {}\n",
+                "Max momentary loudness: {} LUFS\n",
+                "Max short-term loudness: {} LUFS\n",
+                "\n",
+                "Result: {}\n"
+            ),
+            format_timestamp(self.created),
+            self.profile.name,
+            self.profile.target,
+            self.profile.tolerance,
+            self.profile.max_true_peak,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            level(self.integrated),
+            verdict(self.loudness_passed),
+            self.range
+                .map_or_else(|| String::from("-"), |range| format!("{:.1}", range)),
+            level(self.max_true_peak),
+            verdict(self.true_peak_passed),
+            level(self.max_momentary),
+            level(self.max_short_term),
+            verdict(self.passed()),
+        )
+    }
+
+    /// The report as a JSON object. Values that couldn't be measured are `null`.
+    pub fn to_json(&self) -> String {
+        let number = |value: f32| {
+            if value.is_finite() {
+                format!("{:.2}", value)
+            } else {
+                String::from("null")
+            }
+        };
+
+        format!(
+            concat!(
+                "{{\"created\":\"{}\",",
+                "\"profile\":{{\"name\":\"{}\",\"target\":{},\"tolerance\":{},\"max_true_peak\":{}}},",
+                "\"duration\":{},\"integrated\":{},\"loudness_range\":{},\"max_true_peak\":{},",
+                "\"max_momentary\":{},\"max_short_term\":{},",
+                "\"loudness_passed\":{},\"true_peak_passed\":{},\"passed\":{}}}"
+            ),
+            format_timestamp(self.created),
+            self.profile.name,
+            number(self.profile.target),
+            number(self.profile.tolerance),
+            number(self.profile.max_true_peak),
+            number(self.duration),
+            number(self.integrated),
+            number(self.range.unwrap_or(f32::NAN)),
+            number(self.max_true_peak),
+            number(self.max_momentary),
+            number(self.max_short_term),
+            self.loudness_passed,
+            self.true_peak_passed,
+            self.passed(),
+        )
+    }
+
+    /// Write the report as text and JSON to new files with the extensions `.txt` and `.json`,
+    /// named after the path and the current time so an earlier export is kept.
+    /// Returns the path of the text file.
+    pub fn export(&self, path: &Path) -> io::Result<PathBuf> {
+        let (mut file, path) = create_timestamped(&path.with_extension("txt"))?;
+        file.write_all(self.to_text().as_bytes())?;
+        File::create(path.with_extension("json"))?.write_all(self.to_json().as_bytes())?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// A session of 10s with both inputs at the loudness and true peak
+    fn session(loudness: f32, true_peak: f32) -> LoudnessSession {
+        let mut frame = MeasurementFrame::new(0, 4800, 2);
+        frame.stereo = [ChannelMeasurement {
+            true_peak,
+            momentary: loudness,
+            short_term: loudness,
+            ..ChannelMeasurement::default()
+        }; 2];
+
+        let mut session = LoudnessSession::new(SAMPLE_RATE);
+        session.add_frames(&[frame; 100]);
+        session
+    }
+
+    #[test]
+    fn channels_add_up_to_the_programme_loudness() {
+        let report = session(-26.0, 0.5).report(PROFILES[0]);
+
+        assert_eq!(report.duration, 10.0);
+        assert!((report.integrated + 23.0).abs() < 0.05);
+        assert!((report.max_short_term + 23.0).abs() < 0.05);
+        assert!(report.range.unwrap() < 0.1);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn checks_the_loudness_and_the_true_peak() {
+        let report = session(-30.0, 0.5).report(PROFILES[0]);
+        assert!(!report.loudness_passed);
+        assert!(report.true_peak_passed);
+
+        let report = session(-26.0, 1.0).report(PROFILES[0]);
+        assert!(report.loudness_passed);
+        assert!(!report.true_peak_passed);
+        assert!(!report.passed());
+
+        // A programme at -25 LUFS is too quiet for EBU R128, but within 2 LU of ATSC A/85
+        assert!(!session(-28.0, 0.5).report(PROFILES[0]).loudness_passed);
+        assert!(session(-28.0, 0.5).report(PROFILES[1]).loudness_passed);
+    }
+
+    #[test]
+    fn mid_side_view_does_not_change_the_programme() {
+        // Identical inputs at -26 LUFS have a mid at -26 LUFS and no side at all
+        let mut frame = MeasurementFrame::new(0, 4800, 2);
+        let input = ChannelMeasurement {
+            momentary: -26.0,
+            short_term: -26.0,
+            ..ChannelMeasurement::default()
+        };
+        frame.stereo = [input; 2];
+        frame.channels[0] = input;
+
+        let mut session = LoudnessSession::new(SAMPLE_RATE);
+        session.add_frames(&[frame; 100]);
+
+        assert!((session.report(PROFILES[0]).integrated + 23.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn exports_keep_earlier_reports() {
+        let directory = std::env::temp_dir().join(format!("compliance_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let report = session(-26.0, 0.5).report(PROFILES[0]);
+        let first = report.export(&directory.join("report")).unwrap();
+        let second = report.export(&directory.join("report")).unwrap();
+        let text = fs::read_to_string(&first).unwrap();
+        let json = fs::read_to_string(first.with_extension("json")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(text, report.to_text());
+        assert_eq!(json, report.to_json());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::alarm::AlarmRule;
+use crate::compliance::{LoudnessProfile, PROFILES};
+use crate::cv::{CvScale, CvSource};
+use crate::level_log::LevelLogFormat;
+use crate::meter_new::MeterScale;
+use crate::midi::CcResolution;
+use crate::INPUT_CHANNELS;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The default amount of OSC updates per second
+const DEFAULT_OSC_RATE: f32 = 20.0;
+/// The default amount of MIDI updates per second
+const DEFAULT_MIDI_RATE: f32 = 30.0;
+/// The default amount of WebSocket updates per second
+const DEFAULT_HTTP_RATE: f32 = 10.0;
+/// The default host the HTTP server binds to, so it is only reachable from this machine
+const DEFAULT_HTTP_HOST: &str = "127.0.0.1";
+/// The default level a channel has to stay below to be silent in dBFS
+const DEFAULT_SILENCE_THRESHOLD: f32 = -60.0;
+/// The default time a channel has to stay below the threshold to be silent in seconds
+const DEFAULT_SILENCE_DURATION: f32 = 10.0;
+/// The default file the events are logged to
+const DEFAULT_EVENT_LOG: &str = "jack_meter_events.log";
+/// The default time between two records of the level log in seconds
+const DEFAULT_LEVEL_LOG_INTERVAL: f32 = 1.0;
+/// The default time after which the level log starts a new file in minutes
+const DEFAULT_LEVEL_LOG_ROTATE: f32 = 60.0;
+/// The default controller of the first channel, the first of the undefined controllers
+const DEFAULT_MIDI_CONTROLLER: u8 = 20;
+/// The highest controller a level can be sent on, the ones above are channel mode messages
+const MAX_MIDI_CONTROLLER: u8 = 119;
+/// The highest controller of a 14-bit pair, the ones above carry the least significant bits
+const MAX_MIDI_14BIT_CONTROLLER: u8 = 31;
+/// The lowest and highest amount of updates per second.
+/// Beyond these the time between two updates stops making sense.
+const MIN_RATE: f32 = 0.01;
+const MAX_RATE: f32 = 1000.0;
+/// The shortest and longest times given in seconds, a millisecond and a day
+const MIN_SECONDS: f32 = 0.001;
+const MAX_SECONDS: f32 = 86400.0;
+/// The shortest and longest times given in minutes, a second and a week
+const MIN_MINUTES: f32 = 1.0 / 60.0;
+const MAX_MINUTES: f32 = 10080.0;
+
+/// The usage shown when the arguments can't be parsed
+pub const USAGE: &str = "Usage: jack_meter [OPTIONS]
+
+Options:
+    --osc-target <HOST:PORT>    Send the levels as OSC messages over UDP to this address
+    --osc-rate <HZ>             The amount of OSC updates per second (default 20)
+    --osc-listen <HOST:PORT>    Accept OSC control messages over UDP on this address
+    --midi                      Send the levels as MIDI control changes on the levels_midi_out port
+    --midi-channel <1-16>       The MIDI channel of the level messages (default 1)
+    --midi-cc <0-119>           The controller of the first audio channel, the others follow it (default 20)
+    --midi-14bit                Send 14-bit levels on the controller and the one 32 above it (needs the controllers of all channels below 32)
+    --midi-rate <HZ>            The amount of MIDI updates per second (default 30)
+    --midi-scale <SCALE>        How the levels are mapped onto the controllers, linear or logarithmic (default logarithmic)
+    --cv <SOURCE>               Add output ports with the peak or rms envelope of every channel
+    --cv-scale <SCALE>          Write the envelope linear or db normalised from -60 dB (default db)
+    --http <PORT>               Serve the levels as a page, over WebSocket and as Prometheus metrics on this port
+    --http-host <HOST>          The host the HTTP server binds to (default 127.0.0.1)
+    --http-rate <HZ>            The amount of WebSocket updates per second (default 10)
+    --silence-threshold <DBFS>  The level a channel has to stay below to be silent (default -60)
+    --silence-duration <SEC>    The time a channel has to stay below the threshold to be silent (default 10)
+    --silence-command <CMD>     Run this shell command whenever a channel goes silent or recovers
+    --alarm <RULE>              Raise an alarm when a rule like 'short_term > -18 for 10' holds, can be repeated
+    --event-log <PATH>          Append alarms and silence changes to this file (default jack_meter_events.log)
+    --level-log <PATH>          Log the levels to files named after this path and the time they were started
+    --level-log-format <FORMAT> Write the level log as csv or jsonl (default csv)
+    --level-log-interval <SEC>  The time between two records of the level log (default 1)
+    --level-log-rotate <MIN>    Start a new level log file after this many minutes (default 60)
+    --report-profile <PROFILE>  The target of the compliance report, ebu, atsc, spotify, apple or youtube (default ebu)
+    --help                      Show this message";
+
+/// The settings of the OSC output
+#[derive(Debug, Clone)]
+pub struct OscConfig {
+    /// The address the messages are sent to, as `host:port`
+    pub target: String,
+    /// The amount of updates per second
+    pub rate: f32,
+}
+
+/// The settings of the MIDI output
+#[derive(Debug, Clone)]
+pub struct MidiConfig {
+    /// The MIDI channel, from 0 to 15
+    pub channel: u8,
+    /// The controller of the first audio channel
+    pub controller: u8,
+    pub resolution: CcResolution,
+    /// The amount of updates per second
+    pub rate: f32,
+    /// The scale the levels are mapped through
+    pub scale: MeterScale,
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            controller: DEFAULT_MIDI_CONTROLLER,
+            resolution: CcResolution::Coarse,
+            rate: DEFAULT_MIDI_RATE,
+            scale: MeterScale::Logarithmic,
+        }
+    }
+}
+
+/// The settings of the control voltage outputs
+#[derive(Debug, Clone)]
+pub struct CvConfig {
+    pub source: CvSource,
+    pub scale: CvScale,
+}
+
+/// The settings of the silence detection
+#[derive(Debug, Clone)]
+pub struct SilenceConfig {
+    /// The level a channel has to stay at or below in dBFS
+    pub threshold: f32,
+    /// The time a channel has to stay below the threshold in seconds
+    pub duration: f32,
+    /// The shell command that is run for every change, if there is one
+    pub command: Option<String>,
+}
+
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_SILENCE_THRESHOLD,
+            duration: DEFAULT_SILENCE_DURATION,
+            command: None,
+        }
+    }
+}
+
+/// The settings of the HTTP server
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// The address the server binds to, as `host:port`
+    pub address: String,
+    /// The amount of WebSocket updates per second
+    pub rate: f32,
+}
+
+/// The settings of the level log
+#[derive(Debug, Clone)]
+pub struct LevelLogConfig {
+    /// The path the names of the files are derived from
+    pub path: PathBuf,
+    pub format: LevelLogFormat,
+    /// The time between two records
+    pub interval: Duration,
+    /// The time after which a new file is started
+    pub rotate: Duration,
+}
+
+/// The settings given on the command line
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The OSC output, if it is enabled
+    pub osc: Option<OscConfig>,
+    /// The address the OSC control input listens on, if it is enabled
+    pub osc_listen: Option<String>,
+    /// The MIDI output, if it is enabled
+    pub midi: Option<MidiConfig>,
+    /// The control voltage outputs, if they are enabled
+    pub cv: Option<CvConfig>,
+    /// The HTTP server, if it is enabled
+    pub http: Option<HttpConfig>,
+    /// The silence detection
+    pub silence: SilenceConfig,
+    /// The rules of the alarm engine
+    pub alarms: Vec<AlarmRule>,
+    /// The file the events are appended to
+    pub event_log: PathBuf,
+    /// The level log, if it is enabled
+    pub level_log: Option<LevelLogConfig>,
+    /// The profile the compliance report checks against at the start
+    pub report_profile: LoudnessProfile,
+}
+
+/// An argument that couldn't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The help was requested
+    Help,
+    /// An option that doesn't exist
+    UnknownOption(String),
+    /// An option without its value
+    MissingValue(String),
+    /// An option with a value that can't be used
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", USAGE),
+            ConfigError::UnknownOption(option) => {
+                write!(f, "Unknown option {}\n\n{}", option, USAGE)
+            }
+            ConfigError::MissingValue(option) => {
+                write!(f, "Missing value for {}\n\n{}", option, USAGE)
+            }
+            ConfigError::InvalidValue(option, value) => {
+                write!(f, "Invalid value {} for {}\n\n{}", value, option, USAGE)
+            }
+        }
+    }
+}
+
+/// Parse a number in an inclusive range.
+/// This also rejects infinite values and NaN, as they are never in the range.
+fn parse_in_range<T: FromStr + PartialOrd>(
+    option: &str,
+    value: String,
+    min: T,
+    max: T,
+) -> Result<T, ConfigError> {
+    match value.parse::<T>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(number),
+        _ => Err(ConfigError::InvalidValue(option.to_string(), value)),
+    }
+}
+
+impl Config {
+    /// Parse the arguments, without the name of the program
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut osc_target = None;
+        let mut osc_rate = DEFAULT_OSC_RATE;
+        let mut osc_listen = None;
+        let mut midi_enabled = false;
+        let mut midi = MidiConfig::default();
+        let mut cv_source = None;
+        let mut cv_scale = CvScale::Db;
+        let mut http_port = None;
+        let mut http_host = DEFAULT_HTTP_HOST.to_string();
+        let mut http_rate = DEFAULT_HTTP_RATE;
+        let mut silence = SilenceConfig::default();
+        let mut alarms = Vec::new();
+        let mut event_log = PathBuf::from(DEFAULT_EVENT_LOG);
+        let mut level_log_path = None;
+        let mut level_log_format = LevelLogFormat::Csv;
+        let mut level_log_interval = DEFAULT_LEVEL_LOG_INTERVAL;
+        let mut level_log_rotate = DEFAULT_LEVEL_LOG_ROTATE;
+        let mut report_profile = PROFILES[0];
+
+        let mut args = args.into_iter();
+        while let Some(option) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| ConfigError::MissingValue(option.clone()))
+            };
+
+            match option.as_str() {
+                "--help" | "-h" => return Err(ConfigError::Help),
+                "--osc-target" => osc_target = Some(value()?),
+                "--osc-rate" => osc_rate = parse_in_range(&option, value()?, MIN_RATE, MAX_RATE)?,
+                "--osc-listen" => osc_listen = Some(value()?),
+                "--midi" => midi_enabled = true,
+                "--midi-channel" => midi.channel = parse_in_range(&option, value()?, 1, 16)? - 1,
+                "--midi-cc" => {
+                    midi.controller = parse_in_range(&option, value()?, 0, MAX_MIDI_CONTROLLER)?
+                }
+                "--midi-14bit" => midi.resolution = CcResolution::Fine,
+                "--midi-rate" => midi.rate = parse_in_range(&option, value()?, MIN_RATE, MAX_RATE)?,
+                "--cv" => {
+                    cv_source = Some(match value()?.as_str() {
+                        "peak" => CvSource::Peak,
+                        "rms" => CvSource::Rms,
+                        other => {
+                            return Err(ConfigError::InvalidValue(
+                                option.clone(),
+                                other.to_string(),
+                            ))
+                        }
+                    })
+                }
+                "--cv-scale" => {
+                    cv_scale = match value()?.as_str() {
+                        "linear" => CvScale::Linear,
+                        "db" => CvScale::Db,
+                        other => {
+                            return Err(ConfigError::InvalidValue(
+                                option.clone(),
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--midi-scale" => {
+                    midi.scale = match value()?.as_str() {
+                        "linear" => MeterScale::Linear,
+                        "logarithmic" => MeterScale::Logarithmic,
+                        other => {
+                            return Err(ConfigError::InvalidValue(
+                                option.clone(),
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--http" => {
+                    let port = value()?;
+                    match port.parse::<u16>() {
+                        Ok(port) => http_port = Some(port),
+                        Err(_) => return Err(ConfigError::InvalidValue(option.clone(), port)),
+                    }
+                }
+                "--http-host" => http_host = value()?,
+                "--http-rate" => http_rate = parse_in_range(&option, value()?, MIN_RATE, MAX_RATE)?,
+                "--silence-threshold" => {
+                    let threshold = value()?;
+                    match threshold.parse::<f32>() {
+                        Ok(level) if level <= 0.0 => silence.threshold = level,
+                        _ => return Err(ConfigError::InvalidValue(option.clone(), threshold)),
+                    }
+                }
+                "--silence-duration" => {
+                    silence.duration = parse_in_range(&option, value()?, MIN_SECONDS, MAX_SECONDS)?
+                }
+                "--silence-command" => silence.command = Some(value()?),
+                "--alarm" => {
+                    let rule = value()?;
+                    match AlarmRule::parse(&rule) {
+                        Some(rule) => alarms.push(rule),
+                        None => return Err(ConfigError::InvalidValue(option.clone(), rule)),
+                    }
+                }
+                "--event-log" => event_log = PathBuf::from(value()?),
+                "--level-log" => level_log_path = Some(PathBuf::from(value()?)),
+                "--level-log-format" => {
+                    level_log_format = match value()?.as_str() {
+                        "csv" => LevelLogFormat::Csv,
+                        "jsonl" => LevelLogFormat::JsonLines,
+                        other => {
+                            return Err(ConfigError::InvalidValue(
+                                option.clone(),
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--level-log-interval" => {
+                    level_log_interval =
+                        parse_in_range(&option, value()?, MIN_SECONDS, MAX_SECONDS)?
+                }
+                "--level-log-rotate" => {
+                    level_log_rotate = parse_in_range(&option, value()?, MIN_MINUTES, MAX_MINUTES)?
+                }
+                "--report-profile" => {
+                    let key = value()?;
+                    match LoudnessProfile::from_key(&key) {
+                        Some(profile) => report_profile = profile,
+                        None => return Err(ConfigError::InvalidValue(option.clone(), key)),
+                    }
+                }
+                _ => return Err(ConfigError::UnknownOption(option.clone())),
+            }
+        }
+
+        // The controllers of all channels have to fit, the 14-bit pairs are only defined for
+        // the controllers below 32
+        let max_controller = match midi.resolution {
+            CcResolution::Coarse => MAX_MIDI_CONTROLLER,
+            CcResolution::Fine => MAX_MIDI_14BIT_CONTROLLER,
+        };
+        if midi.controller as usize + INPUT_CHANNELS - 1 > max_controller as usize {
+            return Err(ConfigError::InvalidValue(
+                "--midi-cc".to_string(),
+                midi.controller.to_string(),
+            ));
+        }
+
+        Ok(Self {
+            osc: osc_target.map(|target| OscConfig {
+                target,
+                rate: osc_rate,
+            }),
+            osc_listen,
+            midi: midi_enabled.then_some(midi),
+            cv: cv_source.map(|source| CvConfig {
+                source,
+                scale: cv_scale,
+            }),
+            http: http_port.map(|port| HttpConfig {
+                address: format!("{}:{}", http_host, port),
+                rate: http_rate,
+            }),
+            silence,
+            alarms,
+            event_log,
+            level_log: level_log_path.map(|path| LevelLogConfig {
+                path,
+                format: level_log_format,
+                interval: Duration::from_secs_f32(level_log_interval),
+                rotate: Duration::from_secs_f32(level_log_rotate * 60.0),
+            }),
+            report_profile,
+        })
+    }
+
+    /// Parse the arguments the program was started with
+    pub fn from_args() -> Result<Self, ConfigError> {
+        Self::parse(std::env::args().skip(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ConfigError> {
+        Config::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn midi_controllers_of_all_channels_stay_valid() {
+        assert!(parse(&["--midi-cc", "118"]).is_ok());
+        // The second channel would land on a channel mode message
+        assert!(parse(&["--midi-cc", "119"]).is_err());
+        assert!(parse(&["--midi-cc", "120"]).is_err());
+
+        assert!(parse(&["--midi-cc", "30", "--midi-14bit"]).is_ok());
+        // The pair of the second channel would start at 32
+        assert!(parse(&["--midi-cc", "31", "--midi-14bit"]).is_err());
+    }
+
+    #[test]
+    fn times_have_to_be_finite() {
+        let config = parse(&["--level-log", "levels.csv", "--level-log-interval", "0.5"]).unwrap();
+        assert_eq!(
+            config.level_log.map(|level_log| level_log.interval),
+            Some(Duration::from_millis(500))
+        );
+
+        for option in [
+            "--level-log-interval",
+            "--level-log-rotate",
+            "--silence-duration",
+        ] {
+            for value in ["inf", "-inf", "NaN", "0", "-1", "1e30"] {
+                assert!(
+                    parse(&["--level-log", "levels.csv", option, value]).is_err(),
+                    "{} {}",
+                    option,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rates_stay_in_a_sane_range() {
+        let config = parse(&["--osc-target", "127.0.0.1:9000", "--osc-rate", "50"]).unwrap();
+        assert_eq!(config.osc.map(|osc| osc.rate), Some(50.0));
+
+        for option in ["--osc-rate", "--midi-rate", "--http-rate"] {
+            // The period between two updates of these would be infinite or zero
+            for value in ["1e-40", "inf", "1e9", "0"] {
+                assert!(parse(&[option, value]).is_err(), "{} {}", option, value);
+            }
+        }
+    }
+}
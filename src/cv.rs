@@ -0,0 +1,201 @@
+use crate::lin2db;
+use crate::meter_new::{smooth, MeterScale};
+
+/// The time constant of the RMS detector in seconds
+const RMS_TIME: f32 = 0.3;
+/// The level that maps to 0 in the dB-normalised output
+const DB_FLOOR: f32 = -60.0;
+
+/// The level an envelope follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvSource {
+    /// The absolute sample values
+    Peak,
+    /// The RMS over about 300ms
+    Rms,
+}
+
+/// How the envelope is written to the output port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvScale {
+    /// The linear level in \[0,1\]
+    Linear,
+    /// The level in dBFS mapped from -60 dB to 0 dB onto \[0,1\]
+    Db,
+}
+
+/// How the display meters smooth their levels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ballistics {
+    /// The smoothing factor of the meters, applied once per update
+    pub smoothing_factor: f32,
+    /// The scale the meters smooth their positions in
+    pub scale: MeterScale,
+    /// The amount of updates of the meters per second
+    pub update_rate: f32,
+}
+
+/// An envelope follower producing a control signal from a channel in the process callback.
+///
+/// The envelope has the same ballistics as the display meters. It smooths the position in the
+/// scale of the meters with their smoothing step, with the factor that is applied once per
+/// display update converted into a per-sample coefficient that gives the same response time.
+pub struct Envelope {
+    source: CvSource,
+    scale: CvScale,
+    sample_rate: f32,
+    /// The ballistics of the display the coefficient was calculated for
+    ballistics: Ballistics,
+    /// The smoothing applied per sample
+    coefficient: f32,
+    /// The coefficient of the RMS detector per sample
+    rms_coefficient: f32,
+    mean_square: f32,
+    /// The current position of the envelope in the scale of the meters
+    pos: f32,
+}
+
+impl Envelope {
+    pub fn new(source: CvSource, scale: CvScale, sample_rate: f32, ballistics: Ballistics) -> Self {
+        let mut envelope = Self {
+            source,
+            scale,
+            sample_rate,
+            ballistics,
+            coefficient: 0.0,
+            rms_coefficient: 1.0 - (-1.0 / (RMS_TIME * sample_rate)).exp(),
+            mean_square: 0.0,
+            pos: 0.0,
+        };
+        envelope.update_coefficient();
+        envelope
+    }
+
+    /// Follow a change of the ballistics of the display
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        if ballistics == self.ballistics {
+            return;
+        }
+
+        // Keep the level where it is when the scale changes
+        let level = self.ballistics.scale.unmap(self.pos);
+        self.pos = ballistics.scale.map(level);
+        self.ballistics = ballistics;
+        self.update_coefficient();
+    }
+
+    fn update_coefficient(&mut self) {
+        // Applying the factor f once per display update leaves (1 - f) of the distance,
+        // spread over all samples of that update
+        let samples_per_update = self.sample_rate / self.ballistics.update_rate.max(1.0);
+        let remaining = 1.0 - self.ballistics.smoothing_factor.clamp(0.0, 1.0);
+        self.coefficient = 1.0 - remaining.powf(1.0 / samples_per_update.max(1.0));
+    }
+
+    /// Follow a process cycle of the channel and write the envelope to the output
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        for (sample, out) in input.iter().zip(output.iter_mut()) {
+            let level = match self.source {
+                CvSource::Peak => sample.abs(),
+                CvSource::Rms => {
+                    self.mean_square += self.rms_coefficient * (sample * sample - self.mean_square);
+                    self.mean_square.sqrt()
+                }
+            };
+
+            let scale = self.ballistics.scale;
+            self.pos = smooth(self.pos, scale.map(level.min(1.0)), self.coefficient);
+            let value = scale.unmap(self.pos);
+
+            *out = match self.scale {
+                CvScale::Linear => value.min(1.0),
+                CvScale::Db => ((lin2db(value) - DB_FLOOR) / -DB_FLOOR).clamp(0.0, 1.0),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    /// The samples of one display update at 60 Hz
+    const UPDATE: usize = 800;
+
+    fn ballistics(scale: MeterScale) -> Ballistics {
+        Ballistics {
+            smoothing_factor: 0.1,
+            scale,
+            update_rate: 60.0,
+        }
+    }
+
+    /// Compare the envelope to a meter that gets the level once per display update
+    fn assert_follows_the_display(scale: MeterScale, levels: &[f32]) {
+        let ballistics = ballistics(scale);
+        let mut envelope = Envelope::new(CvSource::Peak, CvScale::Linear, SAMPLE_RATE, ballistics);
+        let mut output = [0.0; UPDATE];
+        let mut display = 0.0;
+
+        for level in levels {
+            envelope.process(&[*level; UPDATE], &mut output);
+            display = smooth(display, scale.map(*level), ballistics.smoothing_factor);
+
+            let expected = scale.unmap(display);
+            assert!(
+                (output[UPDATE - 1] - expected).abs() < 1e-3,
+                "{} != {}",
+                output[UPDATE - 1],
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn attack_follows_the_display() {
+        assert_follows_the_display(MeterScale::Linear, &[1.0; 20]);
+        assert_follows_the_display(MeterScale::Logarithmic, &[0.5; 20]);
+    }
+
+    #[test]
+    fn release_follows_the_display() {
+        let mut levels = vec![1.0; 60];
+        levels.extend([0.0; 20]);
+        assert_follows_the_display(MeterScale::Linear, &levels);
+        assert_follows_the_display(MeterScale::Logarithmic, &levels);
+    }
+
+    #[test]
+    fn attack_time_depends_on_the_update_rate() {
+        let mut slow = Envelope::new(
+            CvSource::Peak,
+            CvScale::Linear,
+            SAMPLE_RATE,
+            Ballistics {
+                update_rate: 30.0,
+                ..ballistics(MeterScale::Linear)
+            },
+        );
+        let mut output = [0.0; UPDATE * 2];
+
+        // Two updates of a 60 Hz display are a single update at 30 Hz
+        slow.process(&[1.0; UPDATE * 2], &mut output);
+        assert!((output[UPDATE * 2 - 1] - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn db_output_spans_60_db() {
+        let ballistics = Ballistics {
+            smoothing_factor: 1.0,
+            ..ballistics(MeterScale::Linear)
+        };
+        let mut envelope = Envelope::new(CvSource::Peak, CvScale::Db, SAMPLE_RATE, ballistics);
+        let mut output = [0.0; 1];
+
+        envelope.process(&[0.001], &mut output);
+        assert!(output[0].abs() < 1e-3);
+        envelope.process(&[1.0], &mut output);
+        assert!((output[0] - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::lin2db;
+use crate::measurement::MeasurementFrame;
+use crate::meter_new::{Direction, Meter, MeterHandle, MeterScale};
+use std::collections::VecDeque;
+use vizia::*;
+
+/// The length of the window the crest factor is measured over in seconds
+const CREST_WINDOW: f64 = 3.0;
+/// The length of the blocks of the DR measurement in seconds
+const DR_BLOCK: f64 = 3.0;
+/// The fraction of the loudest blocks the DR measurement uses
+const DR_LOUDEST: f64 = 0.2;
+/// The amount of blocks the DR measurement keeps, the oldest are dropped first.
+/// 200 blocks of 3 seconds cover the last 10 minutes.
+const DR_MAX_BLOCKS: usize = 200;
+/// The highest value a dynamics meter shows in dB
+pub const DYNAMICS_RANGE: f32 = 30.0;
+
+/// Measures of how compressed the material is, all in dB.
+/// A value of negative infinity means there wasn't enough signal to measure it yet.
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub struct Dynamics {
+    /// The ratio between the peak and the RMS over the last three seconds
+    pub crest_factor: f32,
+    /// The peak to loudness ratio, the highest true peak minus the integrated loudness
+    pub plr: f32,
+    /// The dynamic range as measured by the DR14 meter
+    pub dr: f32,
+}
+
+impl Default for Dynamics {
+    fn default() -> Self {
+        Self {
+            crest_factor: f32::NEG_INFINITY,
+            plr: f32::NEG_INFINITY,
+            dr: f32::NEG_INFINITY,
+        }
+    }
+}
+
+/// A 3 second block of the DR measurement
+#[derive(Debug, Clone, Copy, Default)]
+struct DrBlock {
+    sum_squares: f64,
+    samples: u64,
+    peak: f32,
+}
+
+impl DrBlock {
+    /// The RMS of the block, scaled up by 3 dB the way the DR meter does it
+    /// so a full scale sine has an RMS of 0 dB
+    fn rms(&self) -> f64 {
+        (2.0 * self.sum_squares / self.samples.max(1) as f64).sqrt()
+    }
+}
+
+/// Collects the [`Dynamics`] of a single channel from the measurement frames
+pub struct DynamicsDetector {
+    /// The frames in the crest factor window
+    /// (samples, peak, sum of squares)
+    window: VecDeque<(u32, f32, f64)>,
+    /// The amount of samples in the crest factor window
+    window_length: u64,
+    /// The highest true peak since the start or the last reset
+    max_true_peak: f32,
+    /// The finished blocks of the DR measurement, at most [`DR_MAX_BLOCKS`]
+    blocks: VecDeque<DrBlock>,
+    /// The block that is being collected
+    current: DrBlock,
+    /// The current values
+    dynamics: Dynamics,
+}
+
+impl DynamicsDetector {
+    pub fn new() -> Self {
+        Self {
+            window: VecDeque::new(),
+            window_length: 0,
+            max_true_peak: 0.0,
+            blocks: VecDeque::new(),
+            current: DrBlock::default(),
+            dynamics: Dynamics::default(),
+        }
+    }
+
+    /// Add the measurements of a channel and return the updated values
+    pub fn add_frames(
+        &mut self,
+        frames: &[MeasurementFrame],
+        channel: usize,
+        sample_rate: f32,
+    ) -> Dynamics {
+        let window_samples = (CREST_WINDOW * sample_rate as f64) as u64;
+        let block_samples = (DR_BLOCK * sample_rate as f64) as u64;
+
+        for frame in frames.iter().filter(|frame| channel < frame.channel_count) {
+            let measurement = &frame.channels[channel];
+            let sum_squares = (measurement.rms as f64).powi(2) * frame.length as f64;
+
+            self.window
+                .push_back((frame.length, measurement.peak, sum_squares));
+            self.window_length += frame.length as u64;
+            while self.window_length > window_samples && self.window.len() > 1 {
+                if let Some((length, _, _)) = self.window.pop_front() {
+                    self.window_length -= length as u64;
+                }
+            }
+
+            self.max_true_peak = self.max_true_peak.max(measurement.true_peak);
+            self.dynamics.plr =
+                level_difference(lin2db(self.max_true_peak), measurement.integrated);
+
+            self.current.sum_squares += sum_squares;
+            self.current.samples += frame.length as u64;
+            self.current.peak = self.current.peak.max(measurement.peak);
+            if self.current.samples >= block_samples {
+                if self.blocks.len() == DR_MAX_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks.push_back(self.current);
+                self.current = DrBlock::default();
+                self.dynamics.dr = self.dr();
+            }
+        }
+
+        let (peak, sum_squares) = self.window.iter().fold(
+            (0.0f32, 0.0f64),
+            |(peak, sum_squares), (_, frame_peak, frame_sum)| {
+                (peak.max(*frame_peak), sum_squares + frame_sum)
+            },
+        );
+        let rms = (sum_squares / self.window_length.max(1) as f64).sqrt() as f32;
+        self.dynamics.crest_factor = level_difference(lin2db(peak), lin2db(rms));
+
+        self.dynamics
+    }
+
+    /// The DR value of all finished blocks
+    fn dr(&self) -> f32 {
+        // The second highest peak is used, so a single stray peak doesn't decide the result
+        let mut peaks: Vec<f32> = self.blocks.iter().map(|block| block.peak).collect();
+        peaks.sort_by(|a, b| b.total_cmp(a));
+        let peak = match peaks.get(1).or_else(|| peaks.first()) {
+            Some(peak) => *peak,
+            None => return f32::NEG_INFINITY,
+        };
+
+        let mut rms: Vec<f64> = self.blocks.iter().map(DrBlock::rms).collect();
+        rms.sort_by(|a, b| b.total_cmp(a));
+        let loudest = ((rms.len() as f64 * DR_LOUDEST).ceil() as usize).max(1);
+        let mean_square = rms[..loudest].iter().map(|rms| rms * rms).sum::<f64>() / loudest as f64;
+
+        level_difference(lin2db(peak), lin2db(mean_square.sqrt() as f32))
+    }
+
+    /// Clear everything collected so far
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// The difference between two levels in dB, or negative infinity if either wasn't measured.
+/// This keeps silence and the start of a measurement from turning into infinite or NaN values.
+fn level_difference(a: f32, b: f32) -> f32 {
+    if a.is_finite() && b.is_finite() {
+        a - b
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// A meter showing a dynamics value from 0 to 30 dB.
+///
+/// As an input it requires a lens to a value in dB, like one of the fields of [`Dynamics`].
+/// The value is shown on a linear scale without smoothing,
+/// coloured from red for heavily compressed to green for very dynamic material.
+///
+/// Example:
+/// ```rust
+/// dynamics_meter(cx, Data::dynamics.map(|dynamics| dynamics.plr), Direction::Right);
+/// ```
+pub fn dynamics_meter<L: Lens<Target = f32>>(
+    cx: &mut Context,
+    lens: L,
+    direction: Direction,
+) -> Handle<Meter> {
+    Meter::new(
+        cx,
+        lens.map(|value| (value / DYNAMICS_RANGE).clamp(0.0, 1.0)),
+        direction,
+    )
+    .scale(MeterScale::Linear)
+    .smoothing_factor(1.0)
+    .sections(vec![
+        (0.0, 8.0 / DYNAMICS_RANGE, vizia::Color::rgb(245, 78, 71)),
+        (
+            8.0 / DYNAMICS_RANGE,
+            14.0 / DYNAMICS_RANGE,
+            vizia::Color::rgb(244, 220, 0),
+        ),
+        (14.0 / DYNAMICS_RANGE, 1.0, vizia::Color::rgb(0, 244, 70)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn frame(peak: f32, integrated: f32) -> MeasurementFrame {
+        let mut frame = MeasurementFrame::new(0, 1024, 1);
+        frame.channels[0].peak = peak;
+        frame.channels[0].true_peak = peak;
+        frame.channels[0].rms = peak / 2.0;
+        frame.channels[0].integrated = integrated;
+        frame
+    }
+
+    #[test]
+    fn silence_is_not_measured() {
+        let mut detector = DynamicsDetector::new();
+        let dynamics = detector.add_frames(&[frame(0.0, f32::NEG_INFINITY); 200], 0, SAMPLE_RATE);
+
+        assert_eq!(dynamics.plr, f32::NEG_INFINITY);
+        assert_eq!(dynamics.crest_factor, f32::NEG_INFINITY);
+        assert_eq!(dynamics.dr, f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn plr_waits_for_the_integrated_loudness() {
+        let mut detector = DynamicsDetector::new();
+        let dynamics = detector.add_frames(&[frame(0.5, f32::NEG_INFINITY)], 0, SAMPLE_RATE);
+        assert_eq!(dynamics.plr, f32::NEG_INFINITY);
+
+        let dynamics = detector.add_frames(&[frame(0.5, -20.0)], 0, SAMPLE_RATE);
+        assert!((dynamics.plr - (lin2db(0.5) + 20.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn dr_blocks_are_limited() {
+        let mut detector = DynamicsDetector::new();
+        let frames = vec![frame(0.5, -20.0); 1000];
+        for _ in 0..40 {
+            detector.add_frames(&frames, 0, SAMPLE_RATE);
+        }
+
+        assert_eq!(detector.blocks.len(), DR_MAX_BLOCKS);
+    }
+}
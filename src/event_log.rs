@@ -0,0 +1,220 @@
+use crate::format_timestamp;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+use vizia::*;
+
+/// The amount of entries the view keeps, the oldest are dropped first
+pub const MAX_ENTRIES: usize = 1000;
+/// The amount of entries the view shows at once
+const VISIBLE_ROWS: usize = 6;
+
+/// Something that happened while monitoring, like an alarm or a silent channel
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The wall-clock time at which it was detected
+    pub time: SystemTime,
+    /// The JACK frame time of the cycle in which it was detected
+    pub frame_time: u32,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(frame_time: u32, message: impl Into<String>) -> Self {
+        Self {
+            time: SystemTime::now(),
+            frame_time,
+            message: message.into(),
+        }
+    }
+
+    /// The entry as a single line with both timestamps
+    pub fn line(&self) -> String {
+        format!(
+            "{}  frame {}  {}",
+            format_timestamp(self.time),
+            self.frame_time,
+            self.message
+        )
+    }
+}
+
+/// Append every entry from the receiver to the log file and pass it on to `ui`.
+///
+/// The file is opened for appending, so the log persists across sessions.
+/// If the file can't be opened, the entries still reach the UI, so the alarms and the
+/// silence detection keep working.
+pub fn spawn_writer(
+    path: &Path,
+    entries: Receiver<LogEntry>,
+    ui: Sender<LogEntry>,
+) -> JoinHandle<()> {
+    let mut file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            eprintln!("Failed to open the event log {}: {}", path.display(), err);
+            None
+        }
+    };
+
+    thread::spawn(move || {
+        while let Ok(entry) = entries.recv() {
+            if let Some(file) = &mut file {
+                if let Err(err) = writeln!(file, "{}", entry.line()) {
+                    eprintln!("Failed to write to the event log: {}", err);
+                }
+            }
+
+            let _ = ui.send(entry);
+        }
+    })
+}
+
+/// The different events that can be called to update states in the event log
+#[derive(Debug, Clone)]
+pub enum EventLogEvents {
+    /// Update the lines of the log
+    Update(Vec<String>),
+    /// Scroll by the amount of lines, positive values scroll towards older entries
+    Scroll(i32),
+    /// Register the rows showing the lines.
+    /// This is sent by the log itself while it is built.
+    #[doc(hidden)]
+    SetRows(Vec<Entity>),
+}
+
+/// A list of log lines with the newest at the bottom.
+///
+/// It follows new lines as they come in. Scrolling back with the mouse wheel keeps the view
+/// on the same lines until it is scrolled back to the bottom.
+///
+/// Example:
+/// ```rust
+/// EventLog::new(cx, Data::event_log);
+/// ```
+pub struct EventLog {
+    lines: Vec<String>,
+    /// The amount of lines the view is scrolled back from the newest
+    offset: usize,
+    /// The labels showing the visible lines from top to bottom
+    rows: Vec<Entity>,
+}
+
+impl EventLog {
+    pub fn new<L: Lens<Target = Vec<String>>>(cx: &mut Context, lens: L) -> Handle<Self> {
+        Self {
+            lines: Vec::new(),
+            offset: 0,
+            rows: Vec::new(),
+        }
+        .build(cx, move |cx| {
+            Binding::new(cx, lens, |cx, lines| {
+                cx.emit(EventLogEvents::Update(lines.get(cx).clone()));
+            });
+
+            let rows = (0..VISIBLE_ROWS)
+                .map(|_| Label::new(cx, "").class("event_log_row").entity)
+                .collect();
+            cx.emit(EventLogEvents::SetRows(rows));
+        })
+    }
+
+    /// The largest offset that still fills all rows
+    fn max_offset(&self) -> usize {
+        self.lines.len().saturating_sub(VISIBLE_ROWS)
+    }
+
+    /// Show the lines at the current offset in the rows
+    fn update_rows(&self, cx: &mut Context) {
+        let end = self.lines.len() - self.offset;
+        let start = end.saturating_sub(VISIBLE_ROWS);
+        let visible = &self.lines[start..end];
+
+        for (index, row) in self.rows.iter().enumerate() {
+            let text = visible.get(index).cloned().unwrap_or_default();
+            cx.style.text.insert(*row, text);
+        }
+        cx.style.needs_redraw = true;
+    }
+}
+
+/// The amount of lines `new` has after the last line of `old`.
+///
+/// Lines are only added at the end and, once the log is full, dropped from the start, so
+/// the length alone doesn't tell how many came in.
+fn added_lines(old: &[String], new: &[String]) -> usize {
+    old.last()
+        .and_then(|last| new.iter().rposition(|line| line == last))
+        .map_or(new.len(), |index| new.len() - index - 1)
+}
+
+impl View for EventLog {
+    fn element(&self) -> Option<String> {
+        Some("event_log".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|event_log_event, _| match event_log_event {
+            EventLogEvents::Update(lines) => {
+                // Stay on the same lines while scrolled back
+                if self.offset > 0 {
+                    self.offset += added_lines(&self.lines, lines);
+                }
+                self.lines = lines.clone();
+                self.offset = self.offset.min(self.max_offset());
+                self.update_rows(cx);
+            }
+            EventLogEvents::Scroll(lines) => {
+                let offset = self.offset as i64 + *lines as i64;
+                self.offset = (offset.max(0) as usize).min(self.max_offset());
+                self.update_rows(cx);
+            }
+            EventLogEvents::SetRows(rows) => {
+                self.rows = rows.clone();
+                self.update_rows(cx);
+            }
+        });
+
+        event.map(|window_event, _| {
+            if let WindowEvent::MouseScroll(_, y) = window_event {
+                cx.emit(EventLogEvents::Scroll(y.round() as i32));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|index| format!("line {}", index)).collect()
+    }
+
+    #[test]
+    fn added_lines_while_growing() {
+        assert_eq!(added_lines(&[], &lines(0..3)), 3);
+        assert_eq!(added_lines(&lines(0..3), &lines(0..3)), 0);
+        assert_eq!(added_lines(&lines(0..3), &lines(0..5)), 2);
+    }
+
+    #[test]
+    fn added_lines_when_old_lines_are_dropped() {
+        assert_eq!(
+            added_lines(&lines(0..MAX_ENTRIES), &lines(1..MAX_ENTRIES + 1)),
+            1
+        );
+        assert_eq!(
+            added_lines(&lines(0..MAX_ENTRIES), &lines(5..MAX_ENTRIES + 5)),
+            5
+        );
+        // All the old lines are gone
+        assert_eq!(
+            added_lines(&lines(0..MAX_ENTRIES), &lines(MAX_ENTRIES..2 * MAX_ENTRIES)),
+            MAX_ENTRIES
+        );
+    }
+}
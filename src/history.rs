@@ -0,0 +1,422 @@
+use crate::lin2db;
+use crate::measurement::MeasurementFrame;
+use std::collections::VecDeque;
+use vizia::vg::{Color, Paint, Path};
+use vizia::*;
+
+/// The shortest time window the history can show in seconds
+const MIN_WINDOW: f32 = 30.0;
+/// The longest time window the history can show and keep in seconds
+const MAX_WINDOW: f32 = 3600.0;
+
+/// A single point in the level history.
+///
+/// One point summarises every measurement frame that arrived since the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub struct HistoryPoint {
+    /// The time of the point in seconds since the client was activated
+    pub time: f32,
+    /// The highest peak in dBFS
+    pub peak: f32,
+    /// The RMS in dBFS
+    pub rms: f32,
+    /// The short-term loudness in LUFS
+    pub short_term: f32,
+}
+
+impl HistoryPoint {
+    /// Summarise a batch of frames for a channel
+    pub fn from_frames(
+        frames: &[MeasurementFrame],
+        channel: usize,
+        sample_rate: f32,
+    ) -> Option<Self> {
+        let last = frames.last()?;
+        if channel >= last.channel_count {
+            return None;
+        }
+
+        let mut peak = 0.0f32;
+        let mut sum = 0.0f32;
+        let mut length = 0u32;
+
+        for frame in frames {
+            let measurement = &frame.channels[channel];
+            peak = peak.max(measurement.peak);
+            sum += measurement.rms * measurement.rms * frame.length as f32;
+            length += frame.length;
+        }
+
+        Some(Self {
+            time: (last.position + last.length as u64) as f32 / sample_rate,
+            peak: lin2db(peak),
+            rms: lin2db((sum / length.max(1) as f32).sqrt()),
+            short_term: last.channels[channel].short_term,
+        })
+    }
+}
+
+/// The different events that can be called to update states in the level history
+#[derive(Debug, Clone)]
+pub enum LevelHistoryEvents {
+    /// Add a new point to the end of the history
+    Push(HistoryPoint),
+    /// Change the time window that is shown in seconds
+    ChangeWindow(f32),
+    /// Change the range of levels that is shown in dB.
+    /// A range whose minimum isn't below its maximum is ignored.
+    ChangeRange(f32, f32),
+    /// Change the horizontal target lines
+    /// (level, colour)
+    ChangeTargets(Vec<(f32, vizia::Color)>),
+    /// Stop or continue following the newest point
+    SetPaused(bool),
+    /// Move the shown window back in time by the amount of seconds.
+    /// Negative values move it forward again. This only moves a paused history.
+    Scroll(f32),
+}
+
+/// A scrolling timeline of the peak, RMS and short-term loudness of a channel.
+///
+/// As an input it requires a lens to the newest [`HistoryPoint`].
+/// Every time the point changes it is appended to the history.
+/// The history keeps up to an hour of points, independently of the window that is shown.
+///
+/// Clicking the view pauses it, so it stops following the newest point.
+/// While paused the mouse wheel scrolls back through the history.
+/// Points keep being recorded while paused.
+///
+/// Example:
+/// ```rust
+/// LevelHistory::new(cx, Data::history)
+/// .window(300.0)
+/// .targets(vec![(-23.0, Color::rgb(255, 255, 255))]);
+/// ```
+#[derive(Lens)]
+pub struct LevelHistory {
+    points: VecDeque<HistoryPoint>,
+    /// The time window that is shown in seconds
+    window: f32,
+    /// The lowest level that is shown in dB
+    min: f32,
+    /// The highest level that is shown in dB
+    max: f32,
+    /// The horizontal target lines
+    /// (level, colour)
+    targets: Vec<(f32, vizia::Color)>,
+    /// The end time of the shown window while paused
+    paused_at: Option<f32>,
+}
+
+impl LevelHistory {
+    pub fn new<L: Lens<Target = HistoryPoint>>(cx: &mut Context, lens: L) -> Handle<Self> {
+        Self {
+            points: VecDeque::new(),
+            window: 60.0,
+            min: -60.0,
+            max: 0.0,
+            targets: Vec::new(),
+            paused_at: None,
+        }
+        .build(cx, move |cx| {
+            Binding::new(cx, lens, |cx, value| {
+                cx.emit(LevelHistoryEvents::Push(*value.get(cx)));
+            });
+        })
+    }
+
+    /// The time of the newest point
+    fn newest(&self) -> f32 {
+        self.points.back().map(|point| point.time).unwrap_or(0.0)
+    }
+
+    /// The time of the oldest point
+    fn oldest(&self) -> f32 {
+        self.points.front().map(|point| point.time).unwrap_or(0.0)
+    }
+
+    /// The end time of the window that should be shown
+    fn end(&self) -> f32 {
+        self.paused_at.unwrap_or_else(|| self.newest())
+    }
+
+    /// Append a point and drop the ones that fell out of the longest window
+    fn push(&mut self, point: HistoryPoint) {
+        // Frames start at zero again when the client restarts
+        if point.time < self.newest() {
+            self.points.clear();
+        }
+
+        self.points.push_back(point);
+
+        let oldest_allowed = point.time - MAX_WINDOW;
+        while self
+            .points
+            .front()
+            .is_some_and(|point| point.time < oldest_allowed)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    /// The end time of the window after scrolling back by the amount of seconds,
+    /// kept between the oldest full window and the newest point
+    fn scrolled_end(&self, seconds: f32) -> f32 {
+        (self.end() - seconds)
+            .min(self.newest())
+            .max((self.oldest() + self.window).min(self.newest()))
+    }
+}
+
+impl View for LevelHistory {
+    fn element(&self) -> Option<String> {
+        Some("level_history".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|history_event, _| match history_event {
+            LevelHistoryEvents::Push(point) => {
+                self.push(*point);
+
+                if self.paused_at.is_none() {
+                    cx.style.needs_redraw = true;
+                }
+            }
+            LevelHistoryEvents::ChangeWindow(window) => {
+                self.window = window.clamp(MIN_WINDOW, MAX_WINDOW);
+                cx.style.needs_redraw = true;
+            }
+            LevelHistoryEvents::ChangeRange(min, max) => {
+                // An empty range would divide by zero when the levels are placed
+                if min < max {
+                    self.min = *min;
+                    self.max = *max;
+                    cx.style.needs_redraw = true;
+                }
+            }
+            LevelHistoryEvents::ChangeTargets(targets) => {
+                self.targets = targets.clone();
+                cx.style.needs_redraw = true;
+            }
+            LevelHistoryEvents::SetPaused(paused) => {
+                self.paused_at = paused.then(|| self.newest());
+                cx.style.needs_redraw = true;
+            }
+            LevelHistoryEvents::Scroll(seconds) => {
+                // Only a click pauses, so scrolling doesn't stop a live history by accident
+                if self.paused_at.is_some() {
+                    self.paused_at = Some(self.scrolled_end(*seconds));
+                    cx.style.needs_redraw = true;
+                }
+            }
+        });
+
+        event.map(|window_event, _| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.emit(LevelHistoryEvents::SetPaused(self.paused_at.is_none()));
+            }
+            WindowEvent::MouseScroll(_, y) => {
+                // One step of the wheel moves the window by a tenth of its length
+                cx.emit(LevelHistoryEvents::Scroll(*y * self.window / 10.0));
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
+        let entity = cx.current();
+
+        let bounds = cx.cache().get_bounds(entity);
+
+        //Skip histories with no width or no height
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.cache().get_opacity(entity);
+
+        let end = self.end();
+        let start = end - self.window;
+
+        let to_x = |time: f32| bounds.x + (time - start) / self.window * bounds.w;
+        let to_y = |level: f32| {
+            let level = level.clamp(self.min, self.max);
+            bounds.y + (self.max - level) / (self.max - self.min) * bounds.h
+        };
+
+        // Draw the target lines below the levels
+        for (level, col) in &self.targets {
+            let mut color: Color = (*col).into();
+            color.set_alphaf(color.a * opacity);
+
+            let mut path = Path::new();
+            path.move_to(bounds.x, to_y(*level));
+            path.line_to(bounds.x + bounds.w, to_y(*level));
+
+            let mut paint = Paint::color(color);
+            paint.set_line_width(1.0);
+            canvas.stroke_path(&mut path, paint);
+        }
+
+        let series: [(fn(&HistoryPoint) -> f32, Color); 3] = [
+            (|point| point.peak, Color::rgb(0, 244, 70)),
+            (|point| point.rms, Color::rgb(244, 220, 0)),
+            (|point| point.short_term, Color::rgb(80, 160, 255)),
+        ];
+
+        for (value, mut color) in series {
+            color.set_alphaf(color.a * opacity);
+
+            // There can be many more points than pixels, so only the highest value
+            // of all points that land on the same pixel column is drawn
+            let mut path = Path::new();
+            let mut column: Option<(f32, f32)> = None;
+            let mut started = false;
+
+            let mut flush = |path: &mut Path, (x, level): (f32, f32)| {
+                if started {
+                    path.line_to(x, to_y(level));
+                } else {
+                    path.move_to(x, to_y(level));
+                    started = true;
+                }
+            };
+
+            for point in self
+                .points
+                .iter()
+                .filter(|point| point.time >= start && point.time <= end)
+            {
+                let x = to_x(point.time).floor();
+                let level = value(point);
+
+                column = match column {
+                    Some((column_x, column_level)) if column_x == x => {
+                        Some((x, column_level.max(level)))
+                    }
+                    Some(previous) => {
+                        flush(&mut path, previous);
+                        Some((x, level))
+                    }
+                    None => Some((x, level)),
+                };
+            }
+
+            if let Some(last) = column {
+                flush(&mut path, last);
+            }
+
+            let mut paint = Paint::color(color);
+            paint.set_line_width(1.0);
+            canvas.stroke_path(&mut path, paint);
+        }
+    }
+}
+
+pub trait LevelHistoryHandle {
+    fn window(self, val: impl Res<f32>) -> Self;
+    fn range(self, min: f32, max: f32) -> Self;
+    fn targets(self, val: impl Res<Vec<(f32, vizia::Color)>>) -> Self;
+}
+
+impl LevelHistoryHandle for Handle<'_, LevelHistory> {
+    fn window(self, val: impl Res<f32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, LevelHistoryEvents::ChangeWindow(value));
+        });
+
+        self
+    }
+
+    fn range(self, min: f32, max: f32) -> Self {
+        self.entity
+            .emit(self.cx, LevelHistoryEvents::ChangeRange(min, max));
+
+        self
+    }
+
+    fn targets(self, val: impl Res<Vec<(f32, vizia::Color)>>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, LevelHistoryEvents::ChangeTargets(value));
+        });
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(time: f32) -> HistoryPoint {
+        HistoryPoint {
+            time,
+            peak: 0.0,
+            rms: 0.0,
+            short_term: 0.0,
+        }
+    }
+
+    /// A history with a point every second up to the end time
+    fn with_points(end: u32) -> LevelHistory {
+        let mut history = LevelHistory {
+            points: VecDeque::new(),
+            window: 60.0,
+            min: -60.0,
+            max: 0.0,
+            targets: Vec::new(),
+            paused_at: None,
+        };
+        for time in 0..=end {
+            history.push(point(time as f32));
+        }
+        history
+    }
+
+    #[test]
+    fn points_summarise_the_frames() {
+        let mut first = MeasurementFrame::new(0, 100, 2);
+        first.channels[1].peak = 0.5;
+        first.channels[1].rms = 0.5;
+        first.channels[1].short_term = -20.0;
+        let mut second = MeasurementFrame::new(100, 300, 2);
+        second.channels[1].peak = 0.25;
+        second.channels[1].short_term = -21.0;
+        let frames = [first, second];
+
+        let point = HistoryPoint::from_frames(&frames, 1, 100.0).unwrap();
+        assert_eq!(point.time, 4.0);
+        assert!((point.peak - lin2db(0.5)).abs() < 1e-4);
+        assert!((point.rms - lin2db(0.25)).abs() < 1e-4);
+        assert_eq!(point.short_term, -21.0);
+
+        assert_eq!(HistoryPoint::from_frames(&frames, 2, 100.0), None);
+        assert_eq!(HistoryPoint::from_frames(&[], 0, 100.0), None);
+    }
+
+    #[test]
+    fn keeps_an_hour_of_points() {
+        let mut history = with_points(4000);
+        assert_eq!(history.oldest(), 4000.0 - MAX_WINDOW);
+        assert_eq!(history.newest(), 4000.0);
+
+        // A restart of the client starts a new history
+        history.push(point(1.0));
+        assert_eq!(history.points.len(), 1);
+    }
+
+    #[test]
+    fn scrolling_stays_within_the_history() {
+        let mut history = with_points(600);
+        history.paused_at = Some(300.0);
+
+        assert_eq!(history.scrolled_end(100.0), 200.0);
+        assert_eq!(history.scrolled_end(-100.0), 400.0);
+        // The oldest window stays full, and the view can't pass the newest point
+        assert_eq!(history.scrolled_end(1000.0), 60.0);
+        assert_eq!(history.scrolled_end(-1000.0), 600.0);
+
+        // A history shorter than the window stays at its newest point
+        assert_eq!(with_points(20).scrolled_end(10.0), 20.0);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::LevelLogConfig;
+use crate::lin2db;
+use crate::measurement::MeasurementFrame;
+use crate::{create_timestamped, format_timestamp};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, SystemTime};
+
+/// The standard the levels are measured by, written to the header of every file
+const METER_STANDARD: &str =
+    "ITU-R BS.1770 / EBU Tech 3341 loudness, sample peak and 4x oversampled true peak in dBFS";
+
+/// The values logged per channel, in the order of the columns
+const COLUMNS: [&str; 5] = ["peak", "true_peak", "rms", "momentary", "short_term"];
+
+/// The format of the level log files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelLogFormat {
+    /// Comma separated values with a row per record
+    Csv,
+    /// A JSON object per line
+    JsonLines,
+}
+
+/// Writes the measurements to a file at a fixed interval, starting a new file every rotation.
+///
+/// Every file starts with a header holding the sample rate, the channel names and the meter
+/// standard. In CSV these are comment lines starting with `#` followed by the column names,
+/// in JSON Lines it is the first object. Levels are in dBFS or LUFS, with silence written as
+/// `-inf` in CSV and `null` in JSON.
+struct LevelLog {
+    config: LevelLogConfig,
+    sample_rate: f32,
+    channel_names: Vec<String>,
+    file: BufWriter<File>,
+    /// When the current file was started
+    started: Instant,
+}
+
+impl LevelLog {
+    fn new(
+        config: LevelLogConfig,
+        sample_rate: f32,
+        channel_names: Vec<String>,
+    ) -> io::Result<Self> {
+        let file = Self::create(&config, sample_rate, &channel_names)?;
+
+        Ok(Self {
+            config,
+            sample_rate,
+            channel_names,
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    /// Create a new file named after the current time and write its header
+    fn create(
+        config: &LevelLogConfig,
+        sample_rate: f32,
+        channel_names: &[String],
+    ) -> io::Result<BufWriter<File>> {
+        // A rotation within the same second gets a counter instead of overwriting the last file
+        let (file, _) = create_timestamped(&config.path)?;
+        let mut file = BufWriter::new(file);
+
+        match config.format {
+            LevelLogFormat::Csv => {
+                writeln!(file, "# sample_rate: {}", sample_rate)?;
+                writeln!(file, "# channels: {}", channel_names.join(","))?;
+                writeln!(file, "# standard: {}", METER_STANDARD)?;
+
+                let mut header = String::from("time,frame_time,position");
+                for name in channel_names {
+                    for column in COLUMNS {
+                        header.push_str(&format!(",{}_{}", name, column));
+                    }
+                }
+                writeln!(file, "{}", header)?;
+            }
+            LevelLogFormat::JsonLines => {
+                let names: Vec<String> = channel_names
+                    .iter()
+                    .map(|name| format!("\"{}\"", name))
+                    .collect();
+                writeln!(
+                    file,
+                    "{{\"sample_rate\":{},\"channels\":[{}],\"standard\":\"{}\"}}",
+                    sample_rate,
+                    names.join(","),
+                    METER_STANDARD
+                )?;
+            }
+        }
+
+        file.flush()?;
+        Ok(file)
+    }
+
+    /// Write a record of the combined frame, rotating the file first if it is due
+    fn write(&mut self, frame: &MeasurementFrame) -> io::Result<()> {
+        // The columns are named after the inputs, so the log shows L/R in the Mid/Side mode too
+        let frame = frame.stereo_frame();
+
+        if self.started.elapsed() >= self.config.rotate {
+            self.file = Self::create(&self.config, self.sample_rate, &self.channel_names)?;
+            self.started = Instant::now();
+        }
+
+        let time = format_timestamp(SystemTime::now());
+        match self.config.format {
+            LevelLogFormat::Csv => {
+                let mut row = format!("{},{},{}", time, frame.frame_time, frame.position);
+                for channel in frame.channels() {
+                    for value in [
+                        lin2db(channel.peak),
+                        lin2db(channel.true_peak),
+                        lin2db(channel.rms),
+                        channel.momentary,
+                        channel.short_term,
+                    ] {
+                        if value.is_finite() {
+                            row.push_str(&format!(",{:.2}", value));
+                        } else {
+                            row.push_str(",-inf");
+                        }
+                    }
+                }
+                writeln!(self.file, "{}", row)?;
+            }
+            LevelLogFormat::JsonLines => {
+                writeln!(
+                    self.file,
+                    "{{\"time\":\"{}\",\"frame_time\":{},\"measurement\":{}}}",
+                    time,
+                    frame.frame_time,
+                    frame.to_json()
+                )?;
+            }
+        }
+
+        // Flushed every record, so the evidence survives the process being killed
+        self.file.flush()
+    }
+}
+
+/// Start logging the frames from the receiver.
+/// The first file is created right away, so a path that can't be written is reported here.
+pub fn spawn_logger(
+    config: &LevelLogConfig,
+    sample_rate: f32,
+    channel_names: Vec<String>,
+    frames: Receiver<MeasurementFrame>,
+) -> io::Result<JoinHandle<()>> {
+    let mut log = LevelLog::new(config.clone(), sample_rate, channel_names)?;
+    let period = config.interval;
+
+    Ok(thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut next_write = Instant::now() + period;
+
+        loop {
+            match frames.recv_timeout(next_write.saturating_duration_since(Instant::now())) {
+                Ok(frame) => pending.push(frame),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if now < next_write {
+                continue;
+            }
+            next_write = (next_write + period).max(now);
+
+            if let Some(frame) = MeasurementFrame::combine(&pending) {
+                if let Err(err) = log.write(&frame) {
+                    eprintln!("Failed to write the level log: {}", err);
+                }
+            }
+            pending.clear();
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn rotations_within_a_second_keep_the_earlier_file() {
+        let directory = std::env::temp_dir().join(format!("level_log_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let config = LevelLogConfig {
+            path: directory.join("levels.csv"),
+            format: LevelLogFormat::Csv,
+            interval: Duration::from_secs(1),
+            rotate: Duration::ZERO,
+        };
+        let names = vec!["L".to_string(), "R".to_string()];
+
+        // Created right after each other, so at least two of them share a second
+        for _ in 0..3 {
+            LevelLog::create(&config, 48000.0, &names).unwrap();
+        }
+        let files = fs::read_dir(&directory).unwrap().count();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(files, 3);
+    }
+}
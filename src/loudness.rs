@@ -0,0 +1,375 @@
+use crate::biquad::{Biquad, Cascade};
+use std::f64::consts::PI;
+
+/// The amount of 100ms blocks in the short-term window
+const SHORT_TERM_BLOCKS: usize = 30;
+/// The amount of 100ms blocks in the momentary window
+const MOMENTARY_BLOCKS: usize = 4;
+
+/// The absolute gate of the integrated loudness in LUFS
+const ABSOLUTE_GATE: f32 = -70.0;
+/// The relative gate of the integrated loudness in LU below the absolutely gated loudness
+const RELATIVE_GATE: f32 = -10.0;
+/// The relative gate of the loudness range in LU below the absolutely gated loudness
+const RANGE_RELATIVE_GATE: f32 = -20.0;
+/// The percentiles of the short-term loudness distribution the loudness range spans
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+/// The highest loudness the gating histogram collects in LUFS
+const HISTOGRAM_MAX: f32 = 10.0;
+/// The width of a bin of the gating histogram in LU
+const HISTOGRAM_RESOLUTION: f32 = 0.1;
+const HISTOGRAM_BINS: usize = ((HISTOGRAM_MAX - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION) as usize;
+
+/// Build the K-weighting pre-filter of ITU-R BS.1770 for an arbitrary sample rate.
+///
+/// The standard only lists coefficients for 48 kHz, so the analogue parameters
+/// of both stages are used to recalculate them for other sample rates.
+pub fn k_weighting(sample_rate: f64) -> Cascade {
+    // Stage 1: high shelf modelling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    let shelf = Biquad::new(
+        (vh + vb * k / q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    // Stage 2: the RLB high pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+
+    let high_pass = Biquad::new(
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    Cascade::new(vec![shelf, high_pass])
+}
+
+/// Convert a mean square of the K-weighted signal to LUFS
+pub fn mean_square_to_lufs(mean_square: f64) -> f32 {
+    (-0.691 + 10.0 * mean_square.log10()) as f32
+}
+
+/// Convert LUFS back to a mean square of the K-weighted signal
+pub fn lufs_to_mean_square(lufs: f32) -> f64 {
+    10f64.powf((lufs as f64 + 0.691) / 10.0)
+}
+
+/// The gated integrated loudness of ITU-R BS.1770.
+///
+/// Instead of keeping every gating block, the block loudness values are
+/// collected in a histogram with a resolution of 0.1 LU.
+/// This keeps the memory fixed, so it can run in the audio thread for any amount of time.
+#[derive(Clone)]
+pub struct IntegratedLoudness {
+    /// The amount of gating blocks per bin, starting at the absolute gate
+    histogram: [u32; HISTOGRAM_BINS],
+}
+
+impl IntegratedLoudness {
+    pub fn new() -> Self {
+        Self {
+            histogram: [0; HISTOGRAM_BINS],
+        }
+    }
+
+    /// Add the mean square of a 400ms gating block
+    pub fn add_block(&mut self, mean_square: f64) {
+        let loudness = mean_square_to_lufs(mean_square);
+        if loudness < ABSOLUTE_GATE {
+            return;
+        }
+
+        let index = ((loudness - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION) as usize;
+        self.histogram[index.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    /// The loudness in the middle of a bin
+    fn bin_loudness(index: usize) -> f32 {
+        ABSOLUTE_GATE + (index as f32 + 0.5) * HISTOGRAM_RESOLUTION
+    }
+
+    /// The mean loudness of all blocks in the bins from `first` on
+    fn gated_mean(&self, first: usize) -> Option<f32> {
+        let mut count = 0u64;
+        let mut sum = 0.0;
+
+        for (index, blocks) in self.histogram.iter().enumerate().skip(first) {
+            count += *blocks as u64;
+            sum += *blocks as f64 * lufs_to_mean_square(Self::bin_loudness(index));
+        }
+
+        (count > 0).then(|| mean_square_to_lufs(sum / count as f64))
+    }
+
+    /// The integrated loudness in LUFS, or negative infinity if no block passed the gates yet
+    pub fn loudness(&self) -> f32 {
+        let ungated = match self.gated_mean(0) {
+            Some(loudness) => loudness,
+            None => return f32::NEG_INFINITY,
+        };
+
+        let relative_gate = ungated + RELATIVE_GATE;
+        let first = ((relative_gate - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION).ceil() as usize;
+
+        self.gated_mean(first).unwrap_or(f32::NEG_INFINITY)
+    }
+
+    pub fn reset(&mut self) {
+        self.histogram = [0; HISTOGRAM_BINS];
+    }
+}
+
+/// The loudness range (LRA) of EBU Tech 3342.
+///
+/// The short-term loudness values are collected in the same kind of histogram as the
+/// integrated loudness, so this also runs for any amount of time with fixed memory.
+#[derive(Clone)]
+pub struct LoudnessRange {
+    /// The amount of short-term values per bin, starting at the absolute gate
+    histogram: [u32; HISTOGRAM_BINS],
+}
+
+impl LoudnessRange {
+    pub fn new() -> Self {
+        Self {
+            histogram: [0; HISTOGRAM_BINS],
+        }
+    }
+
+    /// Add a short-term loudness value in LUFS. These should be taken at least every 100ms.
+    pub fn add(&mut self, short_term: f32) {
+        if short_term < ABSOLUTE_GATE {
+            return;
+        }
+
+        let index = ((short_term - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION) as usize;
+        self.histogram[index.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    /// The loudness range in LU, or `None` if no value passed the gates yet
+    pub fn range(&self) -> Option<f32> {
+        let mut count = 0u64;
+        let mut sum = 0.0;
+        for (index, values) in self.histogram.iter().enumerate() {
+            count += *values as u64;
+            sum += *values as f64 * lufs_to_mean_square(IntegratedLoudness::bin_loudness(index));
+        }
+        if count == 0 {
+            return None;
+        }
+
+        let relative_gate = mean_square_to_lufs(sum / count as f64) + RANGE_RELATIVE_GATE;
+        let first = ((relative_gate - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION)
+            .ceil()
+            .max(0.0) as usize;
+        let gated = &self.histogram[first.min(HISTOGRAM_BINS)..];
+        let total: u64 = gated.iter().map(|values| *values as u64).sum();
+
+        // The loudness of the bin the percentile of the gated values falls into
+        let percentile = |fraction: f64| {
+            let target = (total as f64 * fraction).ceil().max(1.0) as u64;
+            let mut sum = 0u64;
+            for (index, values) in gated.iter().enumerate() {
+                sum += *values as u64;
+                if sum >= target {
+                    return IntegratedLoudness::bin_loudness(first + index);
+                }
+            }
+            IntegratedLoudness::bin_loudness(HISTOGRAM_BINS - 1)
+        };
+
+        (total > 0).then(|| percentile(RANGE_HIGH_PERCENTILE) - percentile(RANGE_LOW_PERCENTILE))
+    }
+
+    pub fn reset(&mut self) {
+        self.histogram = [0; HISTOGRAM_BINS];
+    }
+}
+
+/// A loudness meter for a single channel following ITU-R BS.1770 / EBU Tech 3341.
+///
+/// The signal is K-weighted and collected in 100ms blocks.
+/// The momentary (400ms) and short-term (3s) loudness are the averages over
+/// the last 4 and 30 blocks respectively and update every 100ms.
+/// All buffers are allocated up front, so processing is real-time safe.
+pub struct LoudnessMeter {
+    filter: Cascade,
+    /// The length of one block in samples
+    block_length: usize,
+    /// The sum of squares of the block that is being collected
+    block_sum: f64,
+    /// The amount of samples in the block that is being collected
+    block_position: usize,
+    /// The mean square of the most recent blocks as a ring buffer
+    blocks: [f64; SHORT_TERM_BLOCKS],
+    /// The next position to write to in `blocks`
+    block_index: usize,
+    /// The amount of finished blocks, saturating at the ring buffer size
+    block_count: usize,
+    integrated: IntegratedLoudness,
+    /// Whether finished gating blocks are added to the integrated loudness
+    integrating: bool,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+
+        Self {
+            filter: k_weighting(sample_rate),
+            block_length: (sample_rate / 10.0).round() as usize,
+            block_sum: 0.0,
+            block_position: 0,
+            blocks: [0.0; SHORT_TERM_BLOCKS],
+            block_index: 0,
+            block_count: 0,
+            integrated: IntegratedLoudness::new(),
+            integrating: true,
+        }
+    }
+
+    /// Run a block of samples through the meter
+    pub fn process(&mut self, input: &[f32]) {
+        for sample in input {
+            let y = self.filter.process(*sample as f64);
+            self.block_sum += y * y;
+            self.block_position += 1;
+
+            if self.block_position == self.block_length {
+                let mean_square = self.block_sum / self.block_length as f64;
+
+                self.blocks[self.block_index] = mean_square;
+                self.block_index = (self.block_index + 1) % SHORT_TERM_BLOCKS;
+                self.block_count = (self.block_count + 1).min(SHORT_TERM_BLOCKS);
+
+                self.block_sum = 0.0;
+                self.block_position = 0;
+
+                // Every 100ms block completes a 400ms gating block with 75% overlap
+                if self.integrating && self.block_count >= MOMENTARY_BLOCKS {
+                    self.integrated.add_block(self.window(MOMENTARY_BLOCKS));
+                }
+            }
+        }
+    }
+
+    /// The mean square over the last `count` blocks
+    fn window(&self, count: usize) -> f64 {
+        let count = count.min(self.block_count);
+        if count == 0 {
+            return 0.0;
+        }
+
+        let sum: f64 = (1..=count)
+            .map(|i| self.blocks[(self.block_index + SHORT_TERM_BLOCKS - i) % SHORT_TERM_BLOCKS])
+            .sum();
+        sum / count as f64
+    }
+
+    /// The momentary loudness in LUFS
+    pub fn momentary(&self) -> f32 {
+        mean_square_to_lufs(self.window(MOMENTARY_BLOCKS))
+    }
+
+    /// The short-term loudness in LUFS
+    pub fn short_term(&self) -> f32 {
+        mean_square_to_lufs(self.window(SHORT_TERM_BLOCKS))
+    }
+
+    /// The integrated loudness since the start or the last reset in LUFS
+    pub fn integrated(&self) -> f32 {
+        self.integrated.loudness()
+    }
+
+    /// Start or stop adding to the integrated loudness.
+    /// The momentary and short-term loudness keep updating while it is stopped.
+    pub fn set_integrating(&mut self, integrating: bool) {
+        self.integrating = integrating;
+    }
+
+    /// Clear the integrated loudness, keeping the momentary and short-term loudness
+    pub fn reset_integrated(&mut self) {
+        self.integrated.reset();
+    }
+
+    /// Clear all collected blocks, the integrated loudness and the filter state
+    pub fn reset(&mut self) {
+        self.integrated.reset();
+        self.filter.reset();
+        self.block_sum = 0.0;
+        self.block_position = 0;
+        self.blocks = [0.0; SHORT_TERM_BLOCKS];
+        self.block_index = 0;
+        self.block_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The loudness range of short-term values taken every 100ms for the given seconds per level
+    fn range(steps: &[(f32, usize)]) -> Option<f32> {
+        let mut range = LoudnessRange::new();
+        for &(loudness, seconds) in steps {
+            for _ in 0..seconds * 10 {
+                range.add(loudness);
+            }
+        }
+        range.range()
+    }
+
+    #[test]
+    fn constant_level_has_no_range() {
+        assert!(range(&[(-23.0, 60)]).unwrap().abs() < 0.05);
+    }
+
+    #[test]
+    fn range_of_the_tech_3342_level_steps() {
+        // The steps of the first four test signals of EBU Tech 3342 with their expected range
+        let cases: [(&[(f32, usize)], f32); 4] = [
+            (&[(-20.0, 20), (-30.0, 20)], 10.0),
+            (&[(-20.0, 20), (-15.0, 20)], 5.0),
+            (&[(-40.0, 20), (-20.0, 20)], 20.0),
+            (
+                &[
+                    (-50.0, 10),
+                    (-35.0, 10),
+                    (-20.0, 10),
+                    (-35.0, 10),
+                    (-50.0, 10),
+                ],
+                15.0,
+            ),
+        ];
+
+        for (steps, expected) in cases {
+            let range = range(steps).unwrap();
+            assert!((range - expected).abs() < 0.1, "{} != {}", range, expected);
+        }
+    }
+
+    #[test]
+    fn values_below_the_absolute_gate_are_ignored() {
+        assert_eq!(range(&[(-80.0, 60)]), None);
+        assert!((range(&[(-80.0, 60), (-20.0, 20), (-30.0, 20)]).unwrap() - 10.0).abs() < 0.1);
+    }
+}
@@ -1,20 +1,124 @@
+mod alarm;
+mod biquad;
+mod compliance;
+mod config;
+mod cv;
+mod dynamics;
+mod event_log;
+mod history;
+mod level_log;
+mod loudness;
+mod measurement;
 mod meter;
+mod meter_group;
 mod meter_new;
+mod metrics;
+mod midi;
+mod osc;
+mod rta;
+mod silence;
+mod spectrogram;
+mod spectrum;
+mod statistics;
+mod true_peak;
+mod web;
+mod weighting;
 
-use crate::meter_new::{Direction, Meter, MeterHandle};
+use crate::compliance::{LoudnessProfile, LoudnessSession};
+use crate::config::Config;
+use crate::cv::{Ballistics, Envelope};
+use crate::dynamics::{dynamics_meter, Dynamics, DynamicsDetector};
+use crate::event_log::{EventLog, LogEntry};
+use crate::history::{HistoryPoint, LevelHistory, LevelHistoryHandle};
+use crate::measurement::{
+    mid_side, ChannelAnalyser, CorrelationMeter, Dispatcher, MeasurementFrame,
+};
+use crate::meter_group::{channel_names, MeterGroup, MeterGroupHandle};
+use crate::meter_new::{Direction, MeterEvents, MeterHandle, MeterScale, ReadoutPosition};
+use crate::midi::MidiLevels;
+use crate::osc::OscCommand;
+use crate::rta::{Bandwidth, FilterBank, Rta, RtaHandle, THIRD_OCTAVE_BANDS};
+use crate::silence::SilenceChange;
+use crate::spectrogram::{ColorMap, Spectrogram, SpectrogramHandle};
+use crate::spectrum::{SpectrumAnalyser, SpectrumFrames};
+use crate::statistics::{SessionStatistics, StatisticsEvents, StatisticsPanel};
+use crate::weighting::Weighting;
 use atomic_float::AtomicF32;
 use jack;
-use std::sync::atomic::Ordering;
+use std::cell::{Cell, RefCell};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use vizia::*;
 
 static SENT_VALUE: AtomicF32 = AtomicF32::new(0.0);
+/// Whether the process callback measures the mid and side signals instead of left and right
+static MID_SIDE: AtomicBool = AtomicBool::new(false);
+/// The smoothing factor of the meters, which the control voltage outputs follow
+static SMOOTHING: AtomicF32 = AtomicF32::new(0.1);
+/// Whether the meters use the linear scale, which their smoothing is applied in
+static LINEAR_SCALE: AtomicBool = AtomicBool::new(false);
+/// The amount of times per second the meters are updated, measured by the UI
+static DISPLAY_RATE: AtomicF32 = AtomicF32::new(60.0);
+/// Whether the process callback adds to the integrated loudness
+static INTEGRATING: AtomicBool = AtomicBool::new(true);
+/// Set to make the process callback clear the integrated loudness
+static RESET_INTEGRATED: AtomicBool = AtomicBool::new(false);
+/// The amount of xruns JACK reported since the client was activated
+static XRUNS: AtomicU64 = AtomicU64::new(0);
+
+static BAND_VALUES: [AtomicF32; THIRD_OCTAVE_BANDS] =
+    [const { AtomicF32::new(0.0) }; THIRD_OCTAVE_BANDS];
 
 const STYLE: &str = include_str!("style.css");
 
+/// The amount of samples per spectrum of the spectrogram
+const FFT_SIZE: usize = 2048;
+
+/// The amount of input channels, a stereo pair
+pub const INPUT_CHANNELS: usize = 2;
+
+/// The largest buffer size JACK can be configured with
+const MAX_BUFFER_SIZE: usize = 8192;
+
+/// The path the compliance report is exported to. The time is added to the file name so
+/// every export is kept, and the extensions are added for each format.
+const REPORT_PATH: &str = "jack_meter_report";
+
 #[derive(Lens)]
 pub struct Data {
     input: f32,
+    bands: Vec<f32>,
+    weighting: Weighting,
+    sample_rate: f32,
+    history: HistoryPoint,
+    /// The levels of every channel over the last batch of measurements
+    channels: Vec<HistoryPoint>,
+    spectrum: SpectrumFrames,
+    statistics: SessionStatistics,
+    dynamics: Dynamics,
+    dynamics_detector: DynamicsDetector,
+    /// Whether the meters show mid and side instead of left and right
+    mid_side: bool,
+    /// Whether every channel is currently detected as silent
+    silent: Vec<bool>,
+    /// The lines of the event log, the newest last
+    event_log: Vec<String>,
+    /// The loudness of the session for the compliance report
+    loudness_session: LoudnessSession,
+    /// The profile the compliance report checks against
+    report_profile: LoudnessProfile,
+    /// Counts the resets of all meters, every change resets the meters bound to it
+    meter_reset: u32,
+    /// The settings shared by all meters
+    scale: MeterScale,
+    smoothing: f32,
     drop_speed: f32,
+    hold_time: i32,
+    infinite_hold: bool,
     col: String
 }
 
@@ -25,27 +129,347 @@ impl Model for Data {
                 Events::UpdateValue(n) => {
                     self.input = *n;
                 }
+                Events::UpdateBands(bands) => {
+                    self.bands = bands.clone();
+                }
+                Events::Measurements(frames) => {
+                    if let Some(point) = HistoryPoint::from_frames(frames, 0, self.sample_rate) {
+                        self.history = point;
+                    }
+                    let channel_count = frames.last().map_or(0, |frame| frame.channel_count);
+                    self.channels = (0..channel_count)
+                        .filter_map(|channel| {
+                            HistoryPoint::from_frames(frames, channel, self.sample_rate)
+                        })
+                        .collect();
+                    self.statistics.add_frames(frames, 0, self.sample_rate);
+                    self.dynamics = self
+                        .dynamics_detector
+                        .add_frames(frames, 0, self.sample_rate);
+                    // The report follows the integration of the meters
+                    if INTEGRATING.load(Ordering::Relaxed) {
+                        self.loudness_session.add_frames(frames);
+                    }
+                }
+                Events::Spectra(frames) => {
+                    self.spectrum = SpectrumFrames {
+                        sequence: self.spectrum.sequence + 1,
+                        frames: frames.clone(),
+                    };
+                }
+                Events::Meter(meter_event) => {
+                    self.apply_meter_event(meter_event);
+                }
+                Events::ResetIntegrated => {
+                    RESET_INTEGRATED.store(true, Ordering::Relaxed);
+                    self.loudness_session.reset();
+                    // The PLR compares the highest true peak to the integrated loudness,
+                    // so both have to start over together
+                    self.dynamics_detector.reset();
+                }
+                Events::SetIntegrating(integrating) => {
+                    INTEGRATING.store(*integrating, Ordering::Relaxed);
+                }
+                Events::ToggleMidSide => {
+                    self.mid_side = !self.mid_side;
+                    MID_SIDE.store(self.mid_side, Ordering::Relaxed);
+                }
+                Events::Silence(change) => {
+                    if self.silent.len() <= change.channel {
+                        self.silent.resize(change.channel + 1, false);
+                    }
+                    self.silent[change.channel] = change.silent;
+                }
+                Events::CycleReportProfile => {
+                    self.report_profile = self.report_profile.next();
+                }
+                Events::SetReportProfile(profile) => {
+                    self.report_profile = *profile;
+                }
+                Events::ExportReport => {
+                    let report = self.loudness_session.report(self.report_profile);
+                    let path = Path::new(REPORT_PATH);
+                    if let Err(err) = report.export(path) {
+                        eprintln!("Failed to export the report to {}: {}", path.display(), err);
+                    }
+                }
+                Events::Log(entry) => {
+                    self.event_log.push(entry.line());
+                    if self.event_log.len() > event_log::MAX_ENTRIES {
+                        self.event_log.remove(0);
+                    }
+                }
+            }
+        }
+
+        // R resets the hold, maximum and clip indicator of all meters
+        if let Some(WindowEvent::KeyDown(Code::KeyR, _)) = event.message.downcast() {
+            self.apply_meter_event(&MeterEvents::ResetHold);
+        }
+
+        if let Some(statistics_event) = event.message.downcast() {
+            match statistics_event {
+                StatisticsEvents::Reset => {
+                    self.statistics.reset();
+                }
+                StatisticsEvents::Export(path) => {
+                    if let Err(err) = self.statistics.export(path) {
+                        eprintln!("Failed to export statistics to {}: {}", path.display(), err);
+                    }
+                }
+                StatisticsEvents::ChangeThreshold(threshold) => {
+                    self.statistics.threshold = *threshold;
+                }
             }
         }
     }
 }
 
+impl Data {
+    /// Apply a meter event to the settings shared by all meters.
+    /// The meters are bound to these, so this is how the GUI and the remote control change them.
+    fn apply_meter_event(&mut self, event: &MeterEvents) {
+        match event {
+            MeterEvents::ResetHold => {
+                self.meter_reset = self.meter_reset.wrapping_add(1);
+            }
+            MeterEvents::ChangeMeterScale(scale) => {
+                self.scale = *scale;
+                LINEAR_SCALE.store(*scale == MeterScale::Linear, Ordering::Relaxed);
+            }
+            MeterEvents::ChangeSmoothingFactor(n) => {
+                self.smoothing = *n;
+                SMOOTHING.store(*n, Ordering::Relaxed);
+            }
+            MeterEvents::ChangePeakDropSpeed(n) => {
+                self.drop_speed = *n;
+            }
+            MeterEvents::ChangeMaxHoldTime(n) => {
+                self.hold_time = *n;
+            }
+            MeterEvents::ChangeInfiniteHold(hold) => {
+                self.infinite_hold = *hold;
+            }
+            // The other settings are per meter
+            _ => {}
+        }
+    }
+}
+
 enum Events {
     UpdateValue(f32),
+    UpdateBands(Vec<f32>),
+    Measurements(Vec<MeasurementFrame>),
+    Spectra(Vec<Vec<f32>>),
+    /// Change a setting of all meters or reset them
+    Meter(MeterEvents),
+    /// Clear the integrated loudness of all channels
+    ResetIntegrated,
+    /// Start or stop the integration of the loudness
+    SetIntegrating(bool),
+    /// Switch between measuring left and right or mid and side
+    ToggleMidSide,
+    /// A channel went silent or recovered
+    Silence(SilenceChange),
+    /// Something was written to the event log
+    Log(LogEntry),
+    /// Check the compliance report against the next profile
+    CycleReportProfile,
+    /// Check the compliance report against the profile
+    SetReportProfile(LoudnessProfile),
+    /// Write the compliance report of the session as text and JSON
+    ExportReport,
+}
+
+/// The ballistics of the meters as they are set in the UI
+fn display_ballistics() -> Ballistics {
+    Ballistics {
+        smoothing_factor: SMOOTHING.load(Ordering::Relaxed),
+        scale: if LINEAR_SCALE.load(Ordering::Relaxed) {
+            MeterScale::Linear
+        } else {
+            MeterScale::Logarithmic
+        },
+        update_rate: DISPLAY_RATE.load(Ordering::Relaxed),
+    }
+}
+
+/// Counts the xruns JACK reports, so they can be passed on with the measurements
+struct Notifications;
+
+impl jack::NotificationHandler for Notifications {
+    fn xrun(&mut self, _: &jack::Client) -> jack::Control {
+        XRUNS.fetch_add(1, Ordering::Relaxed);
+        jack::Control::Continue
+    }
 }
 
 fn main() {
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+
     // 1. open a client
     let (client, _status) =
         jack::Client::new("jack_meter", jack::ClientOptions::NO_START_SERVER).unwrap();
 
-    let in_port = client
+    // The first port keeps the name it had before the second one was added,
+    // so existing connections still find it
+    let left_port = client
         .register_port("meter_in", jack::AudioIn::default())
         .unwrap();
+    let right_port = client
+        .register_port("meter_in_2", jack::AudioIn::default())
+        .unwrap();
+
+    let sample_rate = client.sample_rate() as f32;
+    let mut filter_bank = FilterBank::new(Bandwidth::ThirdOctave, sample_rate);
+    // The inputs are always measured, so logs and reports show L/R in the Mid/Side mode too
+    let mut analysers = [
+        ChannelAnalyser::new(sample_rate),
+        ChannelAnalyser::new(sample_rate),
+    ];
+    let mut mid_side_analysers = [
+        ChannelAnalyser::new(sample_rate),
+        ChannelAnalyser::new(sample_rate),
+    ];
+    let mut correlation = CorrelationMeter::new(sample_rate);
+    // The mid and side signals are derived into these, so the process callback doesn't allocate.
+    // They are sized for the largest buffer JACK allows, so a change of the buffer size fits too.
+    let buffer_size = (client.buffer_size() as usize).max(MAX_BUFFER_SIZE);
+    let mut mid = vec![0.0; buffer_size];
+    let mut side = vec![0.0; buffer_size];
+    let mut was_mid_side = false;
+    // The MIDI output only gets a port when it is enabled
+    let mut midi_output: Option<(jack::Port<jack::MidiOut>, MidiLevels)> =
+        config.midi.as_ref().map(|midi_config| {
+            let port = client
+                .register_port("levels_midi_out", jack::MidiOut::default())
+                .unwrap();
+            (port, MidiLevels::new(midi_config.clone(), sample_rate))
+        });
+    // The control voltage outputs with their envelope followers, one per channel
+    let mut cv_outputs: Vec<(jack::Port<jack::AudioOut>, Envelope)> = match &config.cv {
+        Some(cv_config) => ["cv_out_1", "cv_out_2"]
+            .iter()
+            .map(|name| {
+                let port = client
+                    .register_port(name, jack::AudioOut::default())
+                    .unwrap();
+                let envelope = Envelope::new(
+                    cv_config.source,
+                    cv_config.scale,
+                    sample_rate,
+                    display_ballistics(),
+                );
+                (port, envelope)
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let (mut producer, consumer) = measurement::queue();
+    let mut position = 0u64;
+    let mut dispatcher = Dispatcher::new(consumer);
+    let frames_receiver = dispatcher.subscribe();
+    if let Some(osc_config) = &config.osc {
+        if let Err(err) = osc::spawn_sender(osc_config, dispatcher.subscribe()) {
+            eprintln!("Failed to start the OSC output to {}: {}", osc_config.target, err);
+        }
+    }
+    if let Some(http_config) = &config.http {
+        if let Err(err) = web::spawn_server(
+            http_config,
+            sample_rate,
+            config.silence.threshold,
+            dispatcher.subscribe(),
+        ) {
+            eprintln!("Failed to start the HTTP server on {}: {}", http_config.address, err);
+        }
+    }
+    let (log_sender, log_entries) = mpsc::channel();
+    let (logged_sender, logged_entries) = mpsc::channel();
+    event_log::spawn_writer(&config.event_log, log_entries, logged_sender);
+    let (silence_sender, silence_changes) = mpsc::channel();
+    silence::spawn_detector(
+        &config.silence,
+        sample_rate,
+        config.osc.as_ref().map(|osc_config| osc_config.target.clone()),
+        dispatcher.subscribe(),
+        silence_sender,
+        log_sender.clone(),
+    );
+    if let Some(level_log_config) = &config.level_log {
+        if let Err(err) = level_log::spawn_logger(
+            level_log_config,
+            sample_rate,
+            channel_names(INPUT_CHANNELS),
+            dispatcher.subscribe(),
+        ) {
+            eprintln!(
+                "Failed to start the level log at {}: {}",
+                level_log_config.path.display(),
+                err
+            );
+        }
+    }
+    if !config.alarms.is_empty() {
+        alarm::spawn_engine(
+            config.alarms.clone(),
+            sample_rate,
+            dispatcher.subscribe(),
+            log_sender,
+        );
+    }
+    dispatcher.spawn();
+
+    let (command_sender, commands) = mpsc::channel();
+    if let Some(address) = &config.osc_listen {
+        if let Err(err) = osc::spawn_listener(address, command_sender) {
+            eprintln!("Failed to start the OSC control input on {}: {}", address, err);
+        }
+    }
+    let (mut tap, tap_consumer) = spectrum::tap();
+    let tap_consumer = RefCell::new(tap_consumer);
+    let spectrum_analyser = RefCell::new(SpectrumAnalyser::new(FFT_SIZE, FFT_SIZE / 2));
+    let last_idle = Cell::new(Instant::now());
 
     let process = jack::ClosureProcessHandler::new(
-        move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
-            let in_p = in_port.as_slice(ps);
+        move |client: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+            let left = left_port.as_slice(ps);
+            let right = right_port.as_slice(ps);
+
+            let integrating = INTEGRATING.load(Ordering::Relaxed);
+            let reset_integrated = RESET_INTEGRATED.swap(false, Ordering::Relaxed);
+            for analyser in analysers.iter_mut().chain(&mut mid_side_analysers) {
+                analyser.set_integrating(integrating);
+                if reset_integrated {
+                    analyser.reset_integrated();
+                }
+            }
+
+            let mid_side_mode = MID_SIDE.load(Ordering::Relaxed);
+            if mid_side_mode != was_mid_side {
+                // Otherwise the loudness of an earlier Mid/Side run would be integrated into this one
+                for analyser in &mut mid_side_analysers {
+                    analyser.reset();
+                }
+                was_mid_side = mid_side_mode;
+            }
+
+            let inputs = if mid_side_mode {
+                // Cut to the preallocated buffers, in case a larger buffer was ever configured
+                let length = left.len().min(mid.len());
+                mid_side(left, right, &mut mid, &mut side);
+                [&mid[..length], &side[..length]]
+            } else {
+                [left, right]
+            };
+            // The single channel views follow the first channel
+            let in_p = inputs[0];
 
             // Write output
             for val in in_p {
@@ -53,35 +477,239 @@ fn main() {
                 // SENT_VALUE.store(lin2db((*val).abs()) / -80.0, Ordering::Relaxed);
             }
 
+            filter_bank.process(in_p);
+            filter_bank.store(&BAND_VALUES);
+
+            let mut frame = MeasurementFrame::new(position, in_p.len() as u32, inputs.len());
+            frame.frame_time = ps.last_frame_time();
+            // The correlation is about the stereo image, so it is measured on L/R in both modes
+            frame.correlation = correlation.process(left, right);
+            frame.dsp_load = client.cpu_load();
+            frame.xruns = XRUNS.load(Ordering::Relaxed);
+            let stereo = frame.stereo.iter_mut().zip(&mut analysers);
+            for ((measurement, analyser), input) in stereo.zip([left, right]) {
+                *measurement = analyser.measure(input);
+            }
+            if mid_side_mode {
+                let channels = frame.channels.iter_mut().zip(&mut mid_side_analysers);
+                for ((measurement, analyser), input) in channels.zip(inputs) {
+                    *measurement = analyser.measure(input);
+                }
+            } else {
+                frame.channels[..frame.stereo.len()].copy_from_slice(&frame.stereo);
+            }
+            if let Some((port, midi_levels)) = &mut midi_output {
+                midi_levels.process(&inputs, &mut port.writer(ps));
+            }
+
+            let ballistics = display_ballistics();
+            for ((port, envelope), input) in cv_outputs.iter_mut().zip(inputs) {
+                envelope.set_ballistics(ballistics);
+                envelope.process(input, port.as_mut_slice(ps));
+            }
+
+            // If the queue is full the UI isn't keeping up, so the frame is dropped
+            let _ = producer.push(frame);
+            position += in_p.len() as u64;
+
+            for val in in_p {
+                let _ = tap.push(*val);
+            }
+
             // Continue as normal
             jack::Control::Continue
         },
     );
 
     // 4. Activate the client. Also connect the ports to the system audio.
-    let _active_client = client.activate_async((), process).unwrap();
+    let _active_client = client.activate_async(Notifications, process).unwrap();
 
-    Application::new(WindowDescription::new().with_inner_size(300, 300), |cx| {
+    Application::new(WindowDescription::new().with_inner_size(900, 900), move |cx| {
         cx.add_theme(STYLE);
         Data {
             input: 0.0,
-            drop_speed: 0.1,
+            bands: vec![0.0; THIRD_OCTAVE_BANDS],
+            weighting: Weighting::A,
+            sample_rate,
+            history: HistoryPoint {
+                time: 0.0,
+                peak: f32::NEG_INFINITY,
+                rms: f32::NEG_INFINITY,
+                short_term: f32::NEG_INFINITY,
+            },
+            channels: Vec::new(),
+            spectrum: SpectrumFrames {
+                sequence: 0,
+                frames: Vec::new(),
+            },
+            statistics: SessionStatistics::new(-20.0),
+            dynamics: Dynamics::default(),
+            dynamics_detector: DynamicsDetector::new(),
+            mid_side: false,
+            silent: Vec::new(),
+            event_log: Vec::new(),
+            loudness_session: LoudnessSession::new(sample_rate),
+            report_profile: config.report_profile,
+            meter_reset: 0,
+            scale: MeterScale::Logarithmic,
+            smoothing: 0.1,
+            drop_speed: 0.006,
+            hold_time: 20,
+            infinite_hold: false,
             col: String::from("#ffff00")
         }
         .build(cx);
-        VStack::new(cx, |cx| {
-            Label::new(cx, Data::input);
-            Meter::new(cx, Data::input, Direction::Up)
-                .smoothing_factor(0.1)
-                .peak_drop_speed(0.006)
-                .max_hold_time(20)
-                .bar_color(Data::col)
-                .left(Stretch(1.0))
-                .right(Stretch(1.0));
+        HStack::new(cx, |cx| {
+            VStack::new(cx, |cx| {
+                MeterGroup::new(
+                    cx,
+                    Data::channels.map(|channels| {
+                        channels
+                            .iter()
+                            .map(|channel| db2lin(channel.peak))
+                            .collect::<Vec<f32>>()
+                    }),
+                    2,
+                    Direction::Up,
+                    |meter, index| {
+                        meter
+                            .smoothing_factor(Data::smoothing)
+                            .peak_drop_speed(Data::drop_speed)
+                            .max_hold_time(Data::hold_time)
+                            .infinite_hold(Data::infinite_hold)
+                            .bar_color(Data::col)
+                            .readout(ReadoutPosition::End)
+                            .readout_level(Data::channels.map(move |channels| {
+                                channels
+                                    .get(index)
+                                    .map_or(f32::NEG_INFINITY, |channel| channel.rms)
+                            }))
+                            .max_marker(true)
+                            .reset_on_click(true)
+                            .reset(Data::meter_reset)
+                            .silent(Data::silent.map(move |silent| {
+                                silent.get(index).copied().unwrap_or(false)
+                            }))
+                    },
+                )
+                .labels(Data::mid_side.map(|mid_side| {
+                    let names: &[&str] = if *mid_side { &["M", "S"] } else { &["L", "R"] };
+                    names.iter().map(|name| name.to_string()).collect::<Vec<String>>()
+                }))
+                .scale(Data::scale)
+                .gap(4.0)
+                .linked(true);
+                Button::new(
+                    cx,
+                    |cx| cx.emit(Events::Meter(MeterEvents::ResetHold)),
+                    |cx| Label::new(cx, "Reset"),
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(Events::ToggleMidSide),
+                    |cx| {
+                        Label::new(
+                            cx,
+                            Data::mid_side.map(|mid_side| {
+                                String::from(if *mid_side { "M/S" } else { "L/R" })
+                            }),
+                        )
+                    },
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(Events::CycleReportProfile),
+                    |cx| {
+                        Label::new(
+                            cx,
+                            Data::report_profile.map(|profile| String::from(profile.name)),
+                        )
+                    },
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(Events::ExportReport),
+                    |cx| Label::new(cx, "Report"),
+                );
+            })
+            .width(Pixels(120.0));
+            Rta::new(cx, Data::bands, Bandwidth::ThirdOctave).weighting(Data::weighting);
         });
+        LevelHistory::new(cx, Data::history)
+            .window(60.0)
+            .targets(vec![
+                (-23.0, vizia::Color::rgb(255, 255, 255)),
+                (-1.0, vizia::Color::rgb(245, 78, 71)),
+            ]);
+        Spectrogram::new(cx, Data::spectrum, sample_rate, FFT_SIZE)
+            .color_map(ColorMap::Heat)
+            .range(-100.0, 0.0);
+        StatisticsPanel::new(cx, Data::statistics, "jack_meter_statistics.csv");
+        EventLog::new(cx, Data::event_log);
+        HStack::new(cx, |cx| {
+            Label::new(
+                cx,
+                Data::dynamics.map(|dynamics| {
+                    format!(
+                        "Crest {:.1} dB  PLR {:.1} dB  DR {:.0}",
+                        dynamics.crest_factor, dynamics.plr, dynamics.dr
+                    )
+                }),
+            )
+            .width(Pixels(300.0));
+            dynamics_meter(cx, Data::dynamics.map(|dynamics| dynamics.plr), Direction::Right);
+        })
+        .height(Pixels(20.0));
     })
-    .on_idle(|cx| {
+    .on_idle(move |cx| {
+        // The meters take a smoothing step on every update, so the envelopes need to know how often
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_idle.replace(now)).as_secs_f32();
+        if elapsed > 0.0 {
+            let rate = DISPLAY_RATE.load(Ordering::Relaxed);
+            DISPLAY_RATE.store(rate + 0.05 * (1.0 / elapsed - rate), Ordering::Relaxed);
+        }
+
+        while let Ok(command) = commands.try_recv() {
+            cx.emit(match command {
+                OscCommand::Meter(meter_event) => Events::Meter(meter_event),
+                OscCommand::ResetIntegrated => Events::ResetIntegrated,
+                OscCommand::SetIntegrating(integrating) => Events::SetIntegrating(integrating),
+                OscCommand::SetStandard(profile) => Events::SetReportProfile(profile),
+            });
+        }
+
+        while let Ok(change) = silence_changes.try_recv() {
+            cx.emit(Events::Silence(change));
+        }
+        while let Ok(entry) = logged_entries.try_recv() {
+            cx.emit(Events::Log(entry));
+        }
+
         cx.emit(Events::UpdateValue(SENT_VALUE.load(Ordering::Relaxed)));
+        cx.emit(Events::UpdateBands(
+            BAND_VALUES
+                .iter()
+                .map(|band| band.load(Ordering::Relaxed))
+                .collect(),
+        ));
+
+        let mut frames = Vec::new();
+        while let Ok(frame) = frames_receiver.try_recv() {
+            frames.push(frame);
+        }
+        if !frames.is_empty() {
+            cx.emit(Events::Measurements(frames));
+        }
+
+        let mut samples = Vec::new();
+        while let Ok(sample) = tap_consumer.borrow_mut().pop() {
+            samples.push(sample);
+        }
+        let spectra = spectrum_analyser.borrow_mut().push(samples);
+        if !spectra.is_empty() {
+            cx.emit(Events::Spectra(spectra));
+        }
     })
     .run();
 }
@@ -89,3 +717,80 @@ fn main() {
 pub fn lin2db(v: f32) -> f32 {
     20.0 * v.log10()
 }
+
+pub fn db2lin(v: f32) -> f32 {
+    10f32.powf(v / 20.0)
+}
+
+/// The path with the time added to the file name.
+/// `levels.csv` becomes e.g. `levels_2022-03-01T12-00-00Z.csv`, and with an index of 1
+/// `levels_2022-03-01T12-00-00Z_1.csv`.
+pub fn timestamped_path(path: &Path, time: SystemTime, index: usize) -> PathBuf {
+    // Colons aren't allowed in file names everywhere, and the milliseconds are only noise here
+    let timestamp = format_timestamp(time);
+    let mut timestamp = format!("{}Z", &timestamp[..19]).replace(':', "-");
+    if index > 0 {
+        timestamp.push_str(&format!("_{}", index));
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, timestamp, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, timestamp),
+    };
+
+    path.with_file_name(name)
+}
+
+/// Create a new file named after the path and the current time, see [`timestamped_path`].
+/// A file that already exists, e.g. from earlier in the same second, is never overwritten,
+/// the new one gets a counter instead.
+pub fn create_timestamped(path: &Path) -> io::Result<(File, PathBuf)> {
+    let time = SystemTime::now();
+    let mut index = 0;
+    loop {
+        let timestamped = timestamped_path(path, time, index);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&timestamped)
+        {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => index += 1,
+            result => return Ok((result?, timestamped)),
+        }
+    }
+}
+
+/// Format a wall-clock time as an ISO 8601 timestamp in UTC with milliseconds
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Convert the days since the epoch to a date of the proleptic Gregorian calendar
+    // Source: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
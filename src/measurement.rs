@@ -0,0 +1,409 @@
+use crate::lin2db;
+use crate::loudness::LoudnessMeter;
+use crate::true_peak::TruePeak;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The maximum amount of channels a single frame can carry.
+/// The frames are fixed size so they can be sent from the audio thread without allocating.
+pub const MAX_CHANNELS: usize = 8;
+
+/// The amount of frames that can be queued before the audio thread starts dropping them
+const QUEUE_CAPACITY: usize = 2048;
+
+/// The amount of frames that can be waiting for a subscriber before the dispatcher starts
+/// dropping them, so a subscriber that stalls doesn't grow the memory without limit
+const SUBSCRIBER_CAPACITY: usize = 2048;
+
+/// How often the dispatcher checks the queue for new frames
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The time constant of the correlation meter in seconds
+const CORRELATION_TIME: f32 = 0.3;
+
+/// The measurements of a single channel over one process cycle
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelMeasurement {
+    /// The highest absolute sample value
+    pub peak: f32,
+    /// The highest absolute value of the 4x oversampled signal
+    pub true_peak: f32,
+    /// The RMS of the cycle
+    pub rms: f32,
+    /// The momentary loudness in LUFS
+    pub momentary: f32,
+    /// The short-term loudness in LUFS
+    pub short_term: f32,
+    /// The integrated loudness since the start or the last reset in LUFS
+    pub integrated: f32,
+}
+
+impl Default for ChannelMeasurement {
+    fn default() -> Self {
+        Self {
+            peak: 0.0,
+            true_peak: 0.0,
+            rms: 0.0,
+            momentary: f32::NEG_INFINITY,
+            short_term: f32::NEG_INFINITY,
+            integrated: f32::NEG_INFINITY,
+        }
+    }
+}
+
+/// Everything that was measured in one process cycle.
+///
+/// These are produced by the JACK process callback and sent to the non real-time
+/// parts of the application, so every consumer sees exactly the same values.
+#[derive(Debug, Clone, Copy)]
+pub struct MeasurementFrame {
+    /// The position of the first sample of the cycle in samples since the client was activated
+    pub position: u64,
+    /// The JACK frame time of the first sample of the cycle
+    pub frame_time: u32,
+    /// The amount of samples in the cycle
+    pub length: u32,
+    /// The amount of channels in use
+    pub channel_count: usize,
+    /// The measurements per channel. Only the first `channel_count` entries are valid.
+    pub channels: [ChannelMeasurement; MAX_CHANNELS],
+    /// The measurements of the left and right input. These are the first two channels,
+    /// unless the Mid/Side mode is on.
+    pub stereo: [ChannelMeasurement; 2],
+    /// The phase correlation of the left and right input in \[-1,1\], also in the Mid/Side mode
+    pub correlation: f32,
+    /// The load of the JACK engine in percent
+    pub dsp_load: f32,
+    /// The amount of xruns since the client was activated
+    pub xruns: u64,
+}
+
+impl MeasurementFrame {
+    pub fn new(position: u64, length: u32, channel_count: usize) -> Self {
+        Self {
+            position,
+            frame_time: 0,
+            length,
+            channel_count: channel_count.min(MAX_CHANNELS),
+            channels: [ChannelMeasurement::default(); MAX_CHANNELS],
+            stereo: [ChannelMeasurement::default(); 2],
+            correlation: 0.0,
+            dsp_load: 0.0,
+            xruns: 0,
+        }
+    }
+
+    /// The valid channel measurements
+    pub fn channels(&self) -> &[ChannelMeasurement] {
+        &self.channels[..self.channel_count]
+    }
+
+    /// The frame with the left and right input as its channels, whatever the meters show
+    pub fn stereo_frame(&self) -> MeasurementFrame {
+        let mut frame = *self;
+        frame.channel_count = self.stereo.len();
+        frame.channels[..self.stereo.len()].copy_from_slice(&self.stereo);
+        frame
+    }
+
+    /// The frame as a JSON object, with the levels in dBFS and LUFS.
+    /// Levels of negative infinity are written as `null`, since JSON has no infinity.
+    pub fn to_json(self) -> String {
+        let channels: Vec<String> = self
+            .channels()
+            .iter()
+            .map(|channel| {
+                format!(
+                    concat!(
+                        "{{\"peak\":{},\"true_peak\":{},\"rms\":{},",
+                        "\"momentary\":{},\"short_term\":{},\"integrated\":{},\"clip\":{}}}"
+                    ),
+                    json_number(lin2db(channel.peak)),
+                    json_number(lin2db(channel.true_peak)),
+                    json_number(lin2db(channel.rms)),
+                    json_number(channel.momentary),
+                    json_number(channel.short_term),
+                    json_number(channel.integrated),
+                    channel.peak >= 1.0,
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"position\":{},\"length\":{},\"channels\":[{}]}}",
+            self.position,
+            self.length,
+            channels.join(",")
+        )
+    }
+
+    /// Combine consecutive frames into a single frame covering all of them.
+    ///
+    /// Peaks are the highest of all frames, the RMS is averaged over the whole length,
+    /// and the loudness values are taken from the last frame, since they already are averages.
+    /// The correlation and the engine state are taken from the last frame as well.
+    pub fn combine(frames: &[MeasurementFrame]) -> Option<MeasurementFrame> {
+        let first = frames.first()?;
+        let last = frames.last()?;
+
+        let mut combined = MeasurementFrame::new(first.position, 0, last.channel_count);
+        combined.frame_time = first.frame_time;
+        combined.correlation = last.correlation;
+        combined.dsp_load = last.dsp_load;
+        combined.xruns = last.xruns;
+        combined.length = frames.iter().map(|frame| frame.length).sum();
+
+        for channel in 0..combined.channel_count {
+            combined.channels[channel] = combine_channel(frames, |frame| frame.channels[channel]);
+        }
+        for channel in 0..combined.stereo.len() {
+            combined.stereo[channel] = combine_channel(frames, |frame| frame.stereo[channel]);
+        }
+
+        Some(combined)
+    }
+}
+
+/// Combine the measurements of a channel, picked by `channel`, over consecutive frames
+fn combine_channel(
+    frames: &[MeasurementFrame],
+    channel: impl Fn(&MeasurementFrame) -> ChannelMeasurement,
+) -> ChannelMeasurement {
+    let mut combined = ChannelMeasurement::default();
+    let mut sum = 0.0f64;
+    let mut length = 0u64;
+
+    for frame in frames {
+        let measurement = channel(frame);
+        combined.peak = combined.peak.max(measurement.peak);
+        combined.true_peak = combined.true_peak.max(measurement.true_peak);
+        sum += (measurement.rms as f64).powi(2) * frame.length as f64;
+        length += frame.length as u64;
+
+        combined.momentary = measurement.momentary;
+        combined.short_term = measurement.short_term;
+        combined.integrated = measurement.integrated;
+    }
+
+    combined.rms = (sum / length.max(1) as f64).sqrt() as f32;
+    combined
+}
+
+/// A number for JSON with two decimals, or `null` if it isn't finite
+fn json_number(value: f32) -> String {
+    if value.is_finite() {
+        format!("{:.2}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Create the queue that carries the frames from the audio thread to the rest of the application
+pub fn queue() -> (Producer<MeasurementFrame>, Consumer<MeasurementFrame>) {
+    RingBuffer::new(QUEUE_CAPACITY)
+}
+
+/// Fans the frames from the audio thread out to every part of the application that uses them.
+///
+/// The audio thread can only push into a single queue, so the dispatcher drains it on its own thread
+/// and sends a copy of every frame to each subscriber. This keeps slow consumers, like network
+/// outputs, away from both the audio thread and the UI.
+pub struct Dispatcher {
+    consumer: Consumer<MeasurementFrame>,
+    subscribers: Vec<SyncSender<MeasurementFrame>>,
+}
+
+impl Dispatcher {
+    pub fn new(consumer: Consumer<MeasurementFrame>) -> Self {
+        Self {
+            consumer,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Get a receiver for all frames. This has to be done before the dispatcher is started.
+    /// If the receiver falls too far behind, the frames it can't take are dropped.
+    pub fn subscribe(&mut self) -> Receiver<MeasurementFrame> {
+        let (sender, receiver) = sync_channel(SUBSCRIBER_CAPACITY);
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Start passing on the frames until all subscribers are gone
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            while let Ok(frame) = self.consumer.pop() {
+                // Subscribers that hung up are dropped, a full one only misses this frame
+                self.subscribers.retain(|subscriber| {
+                    !matches!(
+                        subscriber.try_send(frame),
+                        Err(TrySendError::Disconnected(_))
+                    )
+                });
+            }
+
+            if self.subscribers.is_empty() {
+                return;
+            }
+
+            thread::sleep(DISPATCH_INTERVAL);
+        })
+    }
+}
+
+/// All detectors that run on a single channel in the audio thread
+pub struct ChannelAnalyser {
+    loudness: LoudnessMeter,
+    true_peak: TruePeak,
+}
+
+impl ChannelAnalyser {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            loudness: LoudnessMeter::new(sample_rate),
+            true_peak: TruePeak::new(),
+        }
+    }
+
+    /// Measure one process cycle of the channel
+    pub fn measure(&mut self, input: &[f32]) -> ChannelMeasurement {
+        let mut peak = 0.0f32;
+        let mut sum = 0.0f32;
+
+        for sample in input {
+            peak = peak.max(sample.abs());
+            sum += sample * sample;
+        }
+
+        self.loudness.process(input);
+
+        ChannelMeasurement {
+            peak,
+            true_peak: self.true_peak.process(input),
+            rms: (sum / input.len().max(1) as f32).sqrt(),
+            momentary: self.loudness.momentary(),
+            short_term: self.loudness.short_term(),
+            integrated: self.loudness.integrated(),
+        }
+    }
+
+    /// Start or stop the integration of the loudness
+    pub fn set_integrating(&mut self, integrating: bool) {
+        self.loudness.set_integrating(integrating);
+    }
+
+    /// Clear the integrated loudness
+    pub fn reset_integrated(&mut self) {
+        self.loudness.reset_integrated();
+    }
+
+    /// Clear the state of all detectors, e.g. when the channel starts carrying a different signal
+    pub fn reset(&mut self) {
+        self.loudness.reset();
+        self.true_peak.reset();
+    }
+}
+
+/// The phase correlation of a pair of channels, averaged over about 300ms.
+///
+/// This is +1 for identical signals, 0 for unrelated ones and -1 for signals with inverted polarity.
+/// The products are averaged per sample, so it is real-time safe.
+pub struct CorrelationMeter {
+    /// The averaging coefficient per sample
+    coefficient: f32,
+    /// The averaged product of both channels
+    product: f32,
+    /// The averaged squares of both channels
+    left_square: f32,
+    right_square: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            coefficient: 1.0 - (-1.0 / (CORRELATION_TIME * sample_rate)).exp(),
+            product: 0.0,
+            left_square: 0.0,
+            right_square: 0.0,
+        }
+    }
+
+    /// Measure one process cycle of the pair and return the correlation at its end
+    pub fn process(&mut self, left: &[f32], right: &[f32]) -> f32 {
+        for (l, r) in left.iter().zip(right) {
+            self.product += self.coefficient * (l * r - self.product);
+            self.left_square += self.coefficient * (l * l - self.left_square);
+            self.right_square += self.coefficient * (r * r - self.right_square);
+        }
+
+        let energy = (self.left_square * self.right_square).sqrt();
+        // Silence has no phase, so it counts as uncorrelated
+        if energy > f32::EPSILON {
+            (self.product / energy).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.product = 0.0;
+        self.left_square = 0.0;
+        self.right_square = 0.0;
+    }
+}
+
+/// Derive the mid (L+R)/2 and side (L-R)/2 signals of a stereo pair.
+/// The output buffers have to be at least as long as the input.
+pub fn mid_side(left: &[f32], right: &[f32], mid: &mut [f32], side: &mut [f32]) {
+    for (((l, r), m), s) in left.iter().zip(right).zip(mid.iter_mut()).zip(side.iter_mut()) {
+        *m = (l + r) / 2.0;
+        *s = (l - r) / 2.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(peak: f32, rms: f32, short_term: f32) -> ChannelMeasurement {
+        ChannelMeasurement {
+            peak,
+            rms,
+            short_term,
+            ..ChannelMeasurement::default()
+        }
+    }
+
+    #[test]
+    fn combine_covers_the_view_and_the_stereo_measurements() {
+        let mut first = MeasurementFrame::new(0, 100, 2);
+        first.channels[0] = measurement(0.5, 0.5, -20.0);
+        first.stereo[1] = measurement(0.25, 1.0, -30.0);
+        let mut second = MeasurementFrame::new(100, 300, 2);
+        second.channels[0] = measurement(0.25, 0.0, -21.0);
+        second.stereo[1] = measurement(0.5, 0.0, -31.0);
+
+        let combined = MeasurementFrame::combine(&[first, second]).unwrap();
+        assert_eq!(combined.length, 400);
+        assert_eq!(combined.channels[0].peak, 0.5);
+        assert_eq!(combined.channels[0].rms, 0.25);
+        assert_eq!(combined.channels[0].short_term, -21.0);
+        assert_eq!(combined.stereo[1].peak, 0.5);
+        assert_eq!(combined.stereo[1].rms, 0.5);
+        assert_eq!(combined.stereo[1].short_term, -31.0);
+    }
+
+    #[test]
+    fn stereo_frame_shows_the_inputs() {
+        let mut frame = MeasurementFrame::new(0, 100, 2);
+        frame.channels[0] = measurement(0.5, 0.0, -20.0);
+        frame.stereo[0] = measurement(0.25, 0.0, -23.0);
+
+        let stereo = frame.stereo_frame();
+        assert_eq!(stereo.channels().len(), 2);
+        assert_eq!(stereo.channels()[0].peak, 0.25);
+        assert_eq!(stereo.channels()[0].short_term, -23.0);
+    }
+}
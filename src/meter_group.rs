@@ -0,0 +1,557 @@
+use crate::meter_new::{Direction, Meter, MeterEvents, MeterScale};
+use vizia::vg::{Color, Paint, Path};
+use vizia::*;
+
+/// The size of the channel labels along the meters in pixels
+const LABEL_SIZE: f32 = 16.0;
+/// The width of the ruler next to vertical meters, or the height above horizontal ones, in pixels
+const RULER_SIZE: f32 = 28.0;
+/// The length of the tick lines of the ruler in pixels
+const TICK_LENGTH: f32 = 4.0;
+/// How far the ruler is inset to line up with the bar inside the border and padding of a meter,
+/// until the style of the meters is known
+const RULER_INSET: f32 = 3.0;
+/// The levels the ruler marks in dBFS
+const RULER_LEVELS: [f32; 10] = [
+    0.0, -3.0, -6.0, -9.0, -12.0, -18.0, -24.0, -30.0, -40.0, -60.0,
+];
+
+/// The usual names of the channels for common channel counts.
+/// Other counts are numbered from 1.
+pub fn channel_names(count: usize) -> Vec<String> {
+    let names: &[&str] = match count {
+        1 => &["Mono"],
+        2 => &["L", "R"],
+        6 => &["L", "R", "C", "LFE", "Ls", "Rs"],
+        8 => &["L", "R", "C", "LFE", "Ls", "Rs", "Lb", "Rb"],
+        _ => return (1..=count).map(|channel| channel.to_string()).collect(),
+    };
+
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+/// The child elements of a meter group
+#[derive(Debug, Clone)]
+pub struct MeterGroupChildren {
+    /// The stack holding the channels, the gap is set on this
+    stack: Entity,
+    meters: Vec<Entity>,
+    labels: Vec<Entity>,
+    ruler: Option<Entity>,
+}
+
+/// The different events that can be called to update states in the meter group
+#[derive(Debug, Clone)]
+pub enum MeterGroupEvents {
+    /// Change the scale of all meters and the ruler
+    ChangeMeterScale(MeterScale),
+    /// Change the names of the channels
+    ChangeLabels(Vec<String>),
+    /// Change the gap between two meters in pixels
+    ChangeGap(f32),
+    /// Link the meters, so resetting one of them resets all of them
+    ChangeLinked(bool),
+    /// Reset the hold, maximum and clip indicator of all meters
+    ResetAll,
+    /// Register the child elements.
+    /// This is sent by the group itself while it is built.
+    #[doc(hidden)]
+    SetChildren(MeterGroupChildren),
+}
+
+/// A group of meters showing several channels side by side.
+///
+/// As an input it requires a lens to a `Vec<f32>` with a linear value in \[0,1\] per channel.
+/// Every meter is created with the same direction and configured by the same closure,
+/// which also gets the index of the channel, so settings only have to be written once.
+///
+/// The channels are labelled with their usual names (L, R, C, LFE, Ls, Rs...),
+/// and bars growing in a straight line get a shared ruler marking the levels in dBFS.
+/// The ruler follows the border and padding of the meters, but doesn't make room for a header readout.
+/// When the meters are linked, clicking one of them resets all of them.
+///
+/// Example:
+/// ```rust
+/// MeterGroup::new(cx, Data::levels, 2, Direction::Up, |meter, _| {
+///     meter.smoothing_factor(0.1).infinite_hold(true)
+/// })
+/// .gap(4.0)
+/// .linked(true);
+/// ```
+pub struct MeterGroup {
+    direction: Direction,
+    linked: bool,
+    children: Option<MeterGroupChildren>,
+    /// How far the ruler is inset at the start and the end to line up with the bars in pixels
+    ruler_inset: (f32, f32),
+}
+
+impl MeterGroup {
+    pub fn new<L, F>(
+        cx: &mut Context,
+        lens: L,
+        channels: usize,
+        direction: Direction,
+        meter: F,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Vec<f32>>,
+        F: 'static + Fn(Handle<Meter>, usize) -> Handle<Meter>,
+    {
+        Self {
+            direction,
+            linked: false,
+            children: None,
+            ruler_inset: (RULER_INSET, RULER_INSET),
+        }
+        .build(cx, move |cx| {
+            let vertical = direction != Direction::Right && direction != Direction::Left;
+            let names = channel_names(channels);
+
+            let mut meters = Vec::new();
+            let mut labels = Vec::new();
+
+            // Every channel is a meter with its label below it, or left of it for horizontal meters
+            let mut channel = |cx: &mut Context, index: usize| {
+                let input = lens
+                    .clone()
+                    .map(move |values| values.get(index).copied().unwrap_or(0.0));
+
+                if vertical {
+                    VStack::new(cx, |cx| {
+                        meters.push(meter(Meter::new(cx, input, direction), index).entity);
+                        labels.push(
+                            Label::new(cx, names[index].clone())
+                                .class("channel_label")
+                                .height(Pixels(LABEL_SIZE))
+                                .entity,
+                        );
+                    });
+                } else {
+                    HStack::new(cx, |cx| {
+                        labels.push(
+                            Label::new(cx, names[index].clone())
+                                .class("channel_label")
+                                .width(Pixels(LABEL_SIZE * 2.0))
+                                .entity,
+                        );
+                        meters.push(meter(Meter::new(cx, input, direction), index).entity);
+                    });
+                }
+            };
+
+            let mut ruler = None;
+            let stack = if vertical {
+                HStack::new(cx, |cx| {
+                    if direction != Direction::Radial {
+                        ruler = Some(
+                            MeterRuler::new(cx, direction)
+                                .width(Pixels(RULER_SIZE))
+                                .top(Pixels(RULER_INSET))
+                                .bottom(Pixels(LABEL_SIZE + RULER_INSET))
+                                .entity,
+                        );
+                    }
+                    for index in 0..channels {
+                        channel(cx, index);
+                    }
+                })
+                .entity
+            } else {
+                VStack::new(cx, |cx| {
+                    ruler = Some(
+                        MeterRuler::new(cx, direction)
+                            .height(Pixels(RULER_SIZE))
+                            .left(Pixels(LABEL_SIZE * 2.0 + RULER_INSET))
+                            .right(Pixels(RULER_INSET))
+                            .entity,
+                    );
+                    for index in 0..channels {
+                        channel(cx, index);
+                    }
+                })
+                .entity
+            };
+
+            cx.emit(MeterGroupEvents::SetChildren(MeterGroupChildren {
+                stack,
+                meters,
+                labels,
+                ruler,
+            }));
+        })
+    }
+}
+
+/// Send an event to a single child without it bubbling back up to the group
+fn send_to<M: Message>(cx: &mut Context, target: Entity, message: M) {
+    cx.event_queue.push_back(
+        Event::new(message)
+            .target(target)
+            .origin(cx.current)
+            .propagate(Propagation::Direct),
+    );
+}
+
+impl View for MeterGroup {
+    fn element(&self) -> Option<String> {
+        Some("meter_group".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|group_event, _| match group_event {
+            MeterGroupEvents::ChangeMeterScale(scale) => {
+                if let Some(children) = &self.children {
+                    for meter in &children.meters {
+                        send_to(cx, *meter, MeterEvents::ChangeMeterScale(*scale));
+                    }
+                    if let Some(ruler) = children.ruler {
+                        send_to(cx, ruler, MeterRulerEvents::ChangeMeterScale(*scale));
+                    }
+                }
+            }
+            MeterGroupEvents::ChangeLabels(names) => {
+                if let Some(children) = &self.children {
+                    for (label, name) in children.labels.iter().zip(names) {
+                        cx.style.text.insert(*label, name.clone());
+                    }
+                    cx.style.needs_redraw = true;
+                }
+            }
+            MeterGroupEvents::ChangeGap(gap) => {
+                if let Some(children) = &self.children {
+                    if self.direction == Direction::Right || self.direction == Direction::Left {
+                        cx.style.row_between.insert(children.stack, Pixels(*gap));
+                    } else {
+                        cx.style.col_between.insert(children.stack, Pixels(*gap));
+                    }
+                    cx.style.needs_relayout = true;
+                }
+            }
+            MeterGroupEvents::ChangeLinked(linked) => {
+                self.linked = *linked;
+            }
+            MeterGroupEvents::ResetAll => {
+                if let Some(children) = &self.children {
+                    for meter in &children.meters {
+                        send_to(cx, *meter, MeterEvents::ResetHold);
+                    }
+                }
+            }
+            MeterGroupEvents::SetChildren(children) => {
+                self.children = Some(children.clone());
+            }
+        });
+
+        // A reset of one meter bubbles up to the group, which passes it on to the others when linked.
+        // The passed on resets are sent directly, so they don't come back here.
+        event.map(|meter_event, _| match meter_event {
+            MeterEvents::ResetHold => {
+                if self.linked {
+                    cx.emit(MeterGroupEvents::ResetAll);
+                }
+            }
+            // The meters update on every input value, by then their style has been resolved
+            MeterEvents::UpdatePosition(_) => self.align_ruler(cx),
+            _ => {}
+        });
+    }
+}
+
+impl MeterGroup {
+    /// Inset the ruler by the border and padding of the meters, so its levels line up with the bars.
+    /// These are resolved the same way the meter does when it draws itself.
+    fn align_ruler(&mut self, cx: &mut Context) {
+        let (meter, ruler) = match &self.children {
+            Some(MeterGroupChildren {
+                meters,
+                ruler: Some(ruler),
+                ..
+            }) => match meters.first() {
+                Some(meter) => (*meter, *ruler),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let bounds = cx.cache.get_bounds(meter);
+        let border_width = cx
+            .style
+            .border_width
+            .get(meter)
+            .copied()
+            .unwrap_or_default()
+            .value_or(bounds.w.min(bounds.h), 0.0);
+        let padding = |units: Option<&Units>, length: f32| {
+            units.copied().unwrap_or_default().value_or(length, 0.0) + border_width
+        };
+
+        let vertical = self.direction != Direction::Right && self.direction != Direction::Left;
+        let inset = if vertical {
+            (
+                padding(cx.style.child_top.get(meter), bounds.h),
+                padding(cx.style.child_bottom.get(meter), bounds.h),
+            )
+        } else {
+            (
+                padding(cx.style.child_left.get(meter), bounds.w),
+                padding(cx.style.child_right.get(meter), bounds.w),
+            )
+        };
+
+        if inset == self.ruler_inset {
+            return;
+        }
+        self.ruler_inset = inset;
+
+        if vertical {
+            cx.style.top.insert(ruler, Pixels(inset.0));
+            cx.style.bottom.insert(ruler, Pixels(LABEL_SIZE + inset.1));
+        } else {
+            cx.style
+                .left
+                .insert(ruler, Pixels(LABEL_SIZE * 2.0 + inset.0));
+            cx.style.right.insert(ruler, Pixels(inset.1));
+        }
+        cx.style.needs_relayout = true;
+    }
+}
+
+pub trait MeterGroupHandle {
+    fn scale(self, val: impl Res<MeterScale>) -> Self;
+    fn labels(self, val: impl Res<Vec<String>>) -> Self;
+    fn gap(self, val: impl Res<f32>) -> Self;
+    fn linked(self, val: impl Res<bool>) -> Self;
+    fn reset(self, val: impl Res<u32>) -> Self;
+}
+
+impl MeterGroupHandle for Handle<'_, MeterGroup> {
+    fn scale(self, val: impl Res<MeterScale>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterGroupEvents::ChangeMeterScale(value));
+        });
+
+        self
+    }
+
+    fn labels(self, val: impl Res<Vec<String>>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterGroupEvents::ChangeLabels(value));
+        });
+
+        self
+    }
+
+    fn gap(self, val: impl Res<f32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterGroupEvents::ChangeGap(value));
+        });
+
+        self
+    }
+
+    fn linked(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterGroupEvents::ChangeLinked(value));
+        });
+
+        self
+    }
+
+    fn reset(self, val: impl Res<u32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, _| {
+            entity.emit(cx, MeterGroupEvents::ResetAll);
+        });
+
+        self
+    }
+}
+
+/// The different events that can be called to update states in the ruler
+#[derive(Debug, Clone)]
+pub enum MeterRulerEvents {
+    /// Change the scale that is used to place the levels
+    ChangeMeterScale(MeterScale),
+    /// Register the labels of the levels.
+    /// This is sent by the ruler itself while it is built.
+    #[doc(hidden)]
+    SetLabels(Vec<Entity>),
+}
+
+/// A scale next to a row of meters, marking levels in dBFS with a tick and a label.
+///
+/// The levels are placed using the same [`MeterScale`] as the meters,
+/// so they line up with the bars as long as both cover the same length.
+/// The ticks take the `color` of the ruler, the labels are styled with `.ruler_label`.
+pub struct MeterRuler {
+    direction: Direction,
+    scale: MeterScale,
+    /// The labels of the levels in the order of `RULER_LEVELS`
+    labels: Vec<Entity>,
+}
+
+impl MeterRuler {
+    pub fn new(cx: &mut Context, direction: Direction) -> Handle<Self> {
+        Self {
+            direction,
+            scale: MeterScale::Logarithmic,
+            labels: Vec::new(),
+        }
+        .build(cx, |cx| {
+            let labels = RULER_LEVELS
+                .iter()
+                .map(|level| {
+                    Label::new(cx, level.to_string())
+                        .class("ruler_label")
+                        .position_type(PositionType::SelfDirected)
+                        .entity
+                })
+                .collect();
+
+            cx.emit(MeterRulerEvents::SetLabels(labels));
+        })
+    }
+
+    /// Place the labels at the position of their level.
+    /// Across the ruler they fill it, along it their size comes from the `.ruler_label` style,
+    /// so it can be matched to the font size.
+    fn layout_labels(&self, cx: &mut Context) {
+        for (label, level) in self.labels.iter().zip(RULER_LEVELS) {
+            let pos = self.scale.map_db(level);
+
+            // Splitting the free space in the ratio of the position centres the label
+            // close to its level, while keeping the outer labels inside the ruler
+            let (left, right, top, bottom) = match self.direction {
+                Direction::Up => (
+                    Pixels(0.0),
+                    Pixels(TICK_LENGTH),
+                    Stretch(1.0 - pos),
+                    Stretch(pos),
+                ),
+                Direction::Down => (
+                    Pixels(0.0),
+                    Pixels(TICK_LENGTH),
+                    Stretch(pos),
+                    Stretch(1.0 - pos),
+                ),
+                Direction::Right => (
+                    Stretch(pos),
+                    Stretch(1.0 - pos),
+                    Pixels(0.0),
+                    Pixels(TICK_LENGTH),
+                ),
+                Direction::Left => (
+                    Stretch(1.0 - pos),
+                    Stretch(pos),
+                    Pixels(0.0),
+                    Pixels(TICK_LENGTH),
+                ),
+                Direction::Radial => unreachable!("Radial meters don't have a ruler"),
+            };
+
+            cx.style.left.insert(*label, left);
+            cx.style.right.insert(*label, right);
+            cx.style.top.insert(*label, top);
+            cx.style.bottom.insert(*label, bottom);
+            if matches!(self.direction, Direction::Up | Direction::Down) {
+                cx.style.width.insert(*label, Stretch(1.0));
+            } else {
+                cx.style.height.insert(*label, Stretch(1.0));
+            }
+        }
+
+        cx.style.needs_relayout = true;
+        cx.style.needs_redraw = true;
+    }
+}
+
+impl View for MeterRuler {
+    fn element(&self) -> Option<String> {
+        Some("meter_ruler".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|ruler_event, _| match ruler_event {
+            MeterRulerEvents::ChangeMeterScale(scale) => {
+                self.scale = *scale;
+                self.layout_labels(cx);
+            }
+            MeterRulerEvents::SetLabels(labels) => {
+                self.labels = labels.clone();
+                self.layout_labels(cx);
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
+        let entity = cx.current();
+
+        let bounds = cx.cache().get_bounds(entity);
+
+        //Skip rulers with no width or no height
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.cache().get_opacity(entity);
+
+        let mut tick_color: Color = cx
+            .font_color(entity)
+            .cloned()
+            .unwrap_or_else(vizia::Color::white)
+            .into();
+        tick_color.set_alphaf(tick_color.a * opacity);
+
+        // The ticks sit on the edge facing the meters
+        let mut path = Path::new();
+        for level in RULER_LEVELS {
+            let pos = self.scale.map_db(level);
+
+            match self.direction {
+                Direction::Up | Direction::Down => {
+                    let y = if self.direction == Direction::Up {
+                        bounds.y + (1.0 - pos) * bounds.h
+                    } else {
+                        bounds.y + pos * bounds.h
+                    };
+                    path.move_to(bounds.x + bounds.w - TICK_LENGTH, y);
+                    path.line_to(bounds.x + bounds.w, y);
+                }
+                Direction::Right | Direction::Left => {
+                    let x = if self.direction == Direction::Right {
+                        bounds.x + pos * bounds.w
+                    } else {
+                        bounds.x + (1.0 - pos) * bounds.w
+                    };
+                    path.move_to(x, bounds.y + bounds.h - TICK_LENGTH);
+                    path.line_to(x, bounds.y + bounds.h);
+                }
+                Direction::Radial => return,
+            }
+        }
+
+        let mut paint = Paint::color(tick_color);
+        paint.set_line_width(1.0);
+        canvas.stroke_path(&mut path, paint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_layouts_get_their_names() {
+        assert_eq!(channel_names(1), vec!["Mono"]);
+        assert_eq!(channel_names(2), vec!["L", "R"]);
+        assert_eq!(channel_names(6), vec!["L", "R", "C", "LFE", "Ls", "Rs"]);
+        assert_eq!(channel_names(8)[6..], ["Lb", "Rb"]);
+    }
+
+    #[test]
+    fn other_counts_are_numbered() {
+        assert_eq!(channel_names(3), vec!["1", "2", "3"]);
+        assert_eq!(channel_names(4).last().map(String::as_str), Some("4"));
+        assert!(channel_names(0).is_empty());
+    }
+}
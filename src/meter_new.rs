@@ -1,4 +1,5 @@
-use vizia::vg::{Color, Paint, Path};
+use crate::{db2lin, lin2db};
+use vizia::vg::{Color, LineCap, Paint, Path, Solidity};
 use vizia::*;
 
 /// The direction the meter bar shows the peak in.
@@ -14,6 +15,112 @@ pub enum Direction {
     Right,
     /// The inverted direction from the standard horizontal meter
     Left,
+    /// A round meter that grows clockwise along an arc.
+    /// The angles of the arc and its thickness are set using the `arc_angles` and `arc_thickness` handles
+    Radial,
+}
+
+/// The layout of the segments of an LED-ladder style meter
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentLayout {
+    /// The amount of segments, evenly spread across the meter
+    Count(usize),
+    /// The level in dBFS at which each segment lights up, in ascending order.
+    /// Every segment reaches up to the next level, the last one up to the end of the meter.
+    Levels(Vec<f32>),
+}
+
+impl SegmentLayout {
+    /// The positions in \[0,1\] where the segments start and end on a meter with the scale
+    fn bounds(&self, scale: MeterScale) -> Vec<f32> {
+        match self {
+            SegmentLayout::Count(count) => (0..=*count)
+                .map(|i| i as f32 / (*count).max(1) as f32)
+                .collect(),
+            SegmentLayout::Levels(levels) => levels
+                .iter()
+                .map(|level| scale.map_db(*level))
+                .chain(std::iter::once(1.0))
+                .collect(),
+        }
+    }
+}
+
+/// How the sections colour the bar of the meter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum FillMode {
+    /// The sections are turned into a gradient spanning the whole meter,
+    /// blending the colours where sections meet
+    Gradient,
+    /// Every section is filled with its solid colour, up to the current level
+    Zones,
+    /// The whole bar takes the colour of the section the current level is in
+    Level,
+}
+
+/// Where the numeric readout of a meter is placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum ReadoutPosition {
+    /// In a strip above the meter. The bar is shortened to make room for it.
+    Header,
+    /// On top of the track, at the end of the meter the bar grows towards
+    End,
+}
+
+/// The height of the strip the readout takes up in pixels
+const READOUT_HEIGHT: f32 = 32.0;
+/// The width of the readout next to a horizontal meter in pixels
+const READOUT_WIDTH: f32 = 48.0;
+/// Levels below this are shown as "-inf" in dBFS
+const READOUT_FLOOR: f32 = -90.0;
+/// The amount of updates the track stays lit, and then dark, while a silent meter blinks
+const SILENT_BLINK_TICKS: i32 = 30;
+/// The angle in degrees each short arc of the gradient of a radial meter covers
+const ARC_GRADIENT_STEP: f32 = 2.0;
+
+/// The child elements of the numeric readout
+#[derive(Debug, Clone, Copy)]
+pub struct MeterReadout {
+    /// The container holding the labels
+    container: Entity,
+    /// The label showing the held peak
+    peak: Entity,
+    /// The label showing the level set with the `readout_level` handle
+    level: Entity,
+}
+
+/// The hidden child elements a meter reads its style from.
+///
+/// They are never laid out or drawn, but can be targeted from a stylesheet
+/// using selectors like `meter > .peak`.
+#[derive(Debug, Clone, Copy)]
+pub struct MeterParts {
+    /// `.track`: the `background-color` of the unfilled part of the meter
+    track: Entity,
+    /// `.bar`: the `background-color` of the bar where no section applies
+    bar: Entity,
+    /// `.peak`: the `background-color` and `border-width` of the peak line
+    peak: Entity,
+    /// `.max`: the `background-color` of the all-time maximum marker
+    max: Entity,
+    /// `.silent`: the `background-color` the track blinks in while the channel is silent
+    silent: Entity,
+    /// `.clip`: the `background-color` the readout lights up in once the input reached full scale
+    clip: Entity,
+    /// `.low`, `.mid`, `.high` and `.over`: the `background-color` of the default sections
+    sections: [Entity; 4],
+}
+
+/// The colours and sizes of a meter after resolving the handles and the stylesheet
+struct ResolvedStyle {
+    track: Option<vizia::Color>,
+    bar: vizia::Color,
+    line: vizia::Color,
+    line_width: f32,
+    marker: vizia::Color,
+    silent: vizia::Color,
+    clip: vizia::Color,
+    sections: Vec<(f32, f32, vizia::Color)>,
 }
 
 /// The different events that can be called to update states in the meter
@@ -38,10 +145,53 @@ pub enum MeterEvents {
     ChangeLineColor(vizia::Color),
     /// Change the colourd sections
     ChangeSections(Vec<(f32, f32, vizia::Color)>),
+    /// Change the coloured sections using levels in dBFS instead of positions.
+    /// These are mapped through the scale, so they stay at the same level when the scale changes.
+    ChangeLevelSections(Vec<(f32, f32, vizia::Color)>),
+    /// Change the start and end angle of a radial meter in degrees.
+    /// The angles are measured clockwise from the top.
+    ChangeArcAngles(f32, f32),
+    /// Change the thickness of the arc of a radial meter in pixels
+    ChangeArcThickness(f32),
+    /// Change the segments of the meter. `None` draws a continuous bar
+    ChangeSegments(Option<SegmentLayout>),
+    /// Change the gap between two segments in pixels
+    ChangeSegmentGap(f32),
+    /// Change the colours of the unlit segments
+    ChangeUnlitSections(Vec<(f32, f32, vizia::Color)>),
+    /// Change how the sections colour the bar
+    ChangeFillMode(FillMode),
+    /// Change the colour of the unfilled part of the meter
+    ChangeTrackColor(vizia::Color),
+    /// Show a dimmed version of the sections in the unfilled part of the meter
+    /// instead of the track colour
+    ChangeDimmedTrack(bool),
+    /// Show the numeric readout at the position, or hide it with `None`
+    ChangeReadout(Option<ReadoutPosition>),
+    /// Update the second value of the readout, like an RMS or loudness value in dB
+    UpdateReadoutLevel(f32),
+    /// Keep the max peak in place until the meter is reset, instead of letting it drop
+    ChangeInfiniteHold(bool),
+    /// Show a marker at the highest position since the last reset
+    ChangeMaxMarker(bool),
+    /// Reset the meter when it is clicked anywhere, instead of only on the readout
+    ChangeResetOnClick(bool),
+    /// Mark the channel as silent, which makes the track blink until it is cleared
+    ChangeSilent(bool),
+    /// Reset the held peak, the all-time maximum and the clip indicator
+    ResetHold,
+    /// Register the hidden child elements the meter reads its style from.
+    /// This is sent by the meter itself while it is built.
+    #[doc(hidden)]
+    SetParts(MeterParts),
+    /// Register the child elements of the numeric readout.
+    /// This is sent by the meter itself while it is built.
+    #[doc(hidden)]
+    SetReadout(MeterReadout),
 }
 
 /// Different scales to map the values with
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
 pub enum MeterScale {
     /// A linear one to one representation
     Linear,
@@ -50,11 +200,74 @@ pub enum MeterScale {
     Logarithmic,
 }
 
+impl MeterScale {
+    /// Map a linear input value in \[0,1\] to its position on the meter in \[0,1\]
+    pub fn map(&self, value: f32) -> f32 {
+        match self {
+            MeterScale::Linear => value.abs(),
+            MeterScale::Logarithmic => {
+                // Logarithmic approximation for 60db dynamic range
+                // Source: https://www.dr-lex.be/info-stuff/volumecontrols.html
+                value.abs().powf(0.25)
+            }
+        }
+    }
+
+    /// Map a position on the meter in \[0,1\] back to the linear input value in \[0,1\]
+    pub fn unmap(&self, pos: f32) -> f32 {
+        match self {
+            MeterScale::Linear => pos.abs(),
+            MeterScale::Logarithmic => pos.abs().powi(4),
+        }
+    }
+
+    /// Map a level in dBFS to its position on the meter in \[0,1\]
+    pub fn map_db(&self, level: f32) -> f32 {
+        self.map(db2lin(level).min(1.0))
+    }
+
+    /// Map sections given in dBFS to sections in positions on the meter
+    pub fn map_sections(
+        &self,
+        sections: &[(f32, f32, vizia::Color)],
+    ) -> Vec<(f32, f32, vizia::Color)> {
+        sections
+            .iter()
+            .map(|(start, stop, col)| (self.map_db(*start), self.map_db(*stop), *col))
+            .collect()
+    }
+}
+
 /// A meter represents input values in a range of \[0,1\].
 /// As an input it requires a lens. By default it scales the input values logarithmically.
 /// This can be changed using a handle.
 ///
-/// It allows you to show them as a bar that grows in each cardinal direction.
+/// The meter is styled through its hidden parts `.track`, `.bar`, `.peak`, `.max`, `.silent`, `.clip`
+/// and the default sections `.low`, `.mid`, `.high` and `.over`,
+/// e.g. `meter > .peak { background-color: white; }`.
+/// The labels of the readout are styled with `meter > .readout > label`.
+/// Colours set using the handles take precedence over the stylesheet.
+///
+/// The colours of the bar are set with sections. Sections set with the `level_sections` handle are
+/// given in dBFS and keep their level when the scale changes, while sections set with the
+/// `sections` handle are raw positions in \[0,1\]. Meters showing a loudness can use the
+/// `loudness_sections` handle to give them in LU relative to a target loudness instead.
+///
+/// It allows you to show them as a bar that grows in each cardinal direction,
+/// or as an arc that grows clockwise for compact, knob-like meters.
+/// Both can be split into discrete segments to mimic LED-ladder hardware meters.
+///
+/// A numeric readout can be shown above the meter or at the end of the bar using the `readout` handle.
+/// It shows the held peak in dBFS, "OVER" on the colour of the `.clip` part once the input reached
+/// full scale, and optionally a second level set with the `readout_level` handle.
+///
+/// The max peak can be held until the meter is reset using the `infinite_hold` handle,
+/// and the highest position since the last reset can be marked using the `max_marker` handle.
+/// Clicking the readout resets the held peak, the maximum and the clip indicator. With the
+/// `reset_on_click` handle a click anywhere on the meter does the same.
+/// Several meters can be reset together by binding the `reset` handle to a counter.
+/// A channel that is detected as silent can be flagged with the `silent` handle, which makes
+/// the track blink in the colour of the `.silent` part.
 ///
 /// By default it smooths out the input values. The amount of smoothing can be controlled using the `smoothing_factor(f32)` handle.
 /// The value should be in (0,1\] where a value of 1.0 disables smoothing. The lower the value, the stronger the smoothing.
@@ -89,15 +302,60 @@ pub struct Meter {
     smoothing_factor: f32,
     /// The direction the peak meter should grow in
     direction: Direction,
-    /// The colour of the meter bar
-    //NOTE: Replace this by custom style properties once they're implemented
-    bar_color: vizia::Color,
-    /// The colour of the peak line
-    //NOTE: Replace this by custom style properties once they're implemented
-    line_color: vizia::Color,
+    /// The colour of the meter bar, overriding the style of the `.bar` part
+    bar_color: Option<vizia::Color>,
+    /// The colour of the peak line, overriding the style of the `.peak` part
+    line_color: Option<vizia::Color>,
     /// The sections denoting where the bar changes colours
     /// (start, stop, colour)
     sections: Vec<(f32, f32, vizia::Color)>,
+    /// The sections in dBFS that `sections` is calculated from, if they were set by level
+    /// (start, stop, colour)
+    level_sections: Option<Vec<(f32, f32, vizia::Color)>>,
+    /// The angle in degrees at which a radial meter starts, clockwise from the top
+    arc_start: f32,
+    /// The angle in degrees at which a radial meter ends, clockwise from the top
+    arc_end: f32,
+    /// The thickness of the arc of a radial meter in pixels
+    arc_thickness: f32,
+    /// The layout of the LED segments, or `None` for a continuous bar
+    segments: Option<SegmentLayout>,
+    /// The gap between two segments in pixels
+    segment_gap: f32,
+    /// The sections denoting the colours of unlit segments
+    /// (start, stop, colour)
+    /// Segments outside of these use a dimmed version of their lit colour.
+    unlit_sections: Vec<(f32, f32, vizia::Color)>,
+    /// How the sections colour the bar
+    fill_mode: FillMode,
+    /// The colour of the unfilled part of the meter, overriding the style of the `.track` part
+    track_color: Option<vizia::Color>,
+    /// Whether the unfilled part shows a dimmed version of the sections instead of the track colour
+    dimmed_track: bool,
+    /// Where the numeric readout is shown, or `None` to hide it
+    readout: Option<ReadoutPosition>,
+    /// The second value of the readout in dB, if one was set
+    readout_level: Option<f32>,
+    /// Whether the input reached full scale since the last reset
+    clipped: bool,
+    /// Whether the max peak stays in place until the meter is reset
+    infinite_hold: bool,
+    /// Whether the marker of the all-time maximum is shown
+    max_marker: bool,
+    /// Whether a click anywhere on the meter resets it, instead of only on the readout
+    reset_on_click: bool,
+    /// The highest position since the last reset in [0,1]
+    all_time_max: f32,
+    /// Whether the channel is flagged as silent
+    silent: bool,
+    /// A ticker for the blinking of the track while the channel is silent
+    silent_ticker: i32,
+    /// The child elements of the numeric readout
+    readout_parts: Option<MeterReadout>,
+    /// Whether the sections were set using a handle, so the stylesheet doesn't colour them
+    custom_sections: bool,
+    /// The hidden child elements the style is read from
+    parts: Option<MeterParts>,
 }
 
 impl Meter {
@@ -122,15 +380,75 @@ impl Meter {
             max_hold_time: 25,
             smoothing_factor: 0.05,
             direction,
-            bar_color: vizia::Color::red(),
-            line_color: vizia::Color::black(),
+            bar_color: None,
+            line_color: None,
             sections,
+            level_sections: None,
+            arc_start: -135.0,
+            arc_end: 135.0,
+            arc_thickness: 8.0,
+            segments: None,
+            segment_gap: 2.0,
+            unlit_sections: Vec::new(),
+            fill_mode: FillMode::Gradient,
+            track_color: None,
+            dimmed_track: false,
+            readout: None,
+            readout_level: None,
+            clipped: false,
+            infinite_hold: false,
+            max_marker: false,
+            reset_on_click: false,
+            all_time_max: 0.0,
+            silent: false,
+            silent_ticker: 0,
+            readout_parts: None,
+            custom_sections: false,
+            parts: None,
         }
         .build(cx, move |cx| {
             // Bind the input lens to the meter event to update the position
             Binding::new(cx, lens, |cx, value| {
                 cx.emit(MeterEvents::UpdatePosition(value.get(cx)));
             });
+
+            let mut part =
+                |class: &str| Element::new(cx).class(class).display(Display::None).entity;
+
+            let parts = MeterParts {
+                track: part("track"),
+                bar: part("bar"),
+                peak: part("peak"),
+                max: part("max"),
+                silent: part("silent"),
+                clip: part("clip"),
+                sections: [part("low"), part("mid"), part("high"), part("over")],
+            };
+            cx.emit(MeterEvents::SetParts(parts));
+
+            let mut peak = None;
+            let mut level = None;
+            let container = VStack::new(cx, |cx| {
+                peak = Some(Label::new(cx, "-inf").class("readout_peak").entity);
+                level = Some(
+                    Label::new(cx, "")
+                        .class("readout_level")
+                        .display(Display::None)
+                        .entity,
+                );
+            })
+            .class("readout")
+            .position_type(PositionType::SelfDirected)
+            .display(Display::None)
+            .entity;
+
+            if let (Some(peak), Some(level)) = (peak, level) {
+                cx.emit(MeterEvents::SetReadout(MeterReadout {
+                    container,
+                    peak,
+                    level,
+                }));
+            }
         })
     }
 }
@@ -144,14 +462,11 @@ impl View for Meter {
         event.map(|meter_event, _| {
             match meter_event {
                 MeterEvents::UpdatePosition(n) => {
-                    let new_pos = match self.scale {
-                        MeterScale::Linear => (*n).abs(),
-                        MeterScale::Logarithmic => {
-                            // Logarithmic approximation for 60db dynamic range
-                            // Source: https://www.dr-lex.be/info-stuff/volumecontrols.html
-                            (*n).abs().powf(0.25)
-                        }
-                    };
+                    let new_pos = self.scale.map(*n);
+
+                    if n.abs() >= 1.0 {
+                        self.clipped = true;
+                    }
 
                     // Smoothing source: https://stackoverflow.com/a/39417788
                     // Essentially it closes in to the new position by
@@ -159,7 +474,7 @@ impl View for Meter {
                     // and multiplying that by the smoothing_factor.
                     // This a smaller factor causes stronger smoothing.
                     // NOTE: Maybe use (1.0 - smoothing_factor) at some point to allow the factor to create the least amount of smoothing at 0.0
-                    self.pos = self.pos - self.smoothing_factor * (self.pos - new_pos);
+                    self.pos = smooth(self.pos, new_pos, self.smoothing_factor);
 
                     // If the new position is higher than the current max peak update it
                     if self.max < self.pos {
@@ -167,21 +482,37 @@ impl View for Meter {
                         self.max_delay_ticker = self.max_hold_time;
                     }
 
+                    self.all_time_max = self.all_time_max.max(self.pos);
+
                     // Once the ticker for the max peak is done start dropping it until it reaches 0
+                    // With an infinite hold it stays in place until the meter is reset
                     if self.max_delay_ticker == 0 {
-                        self.max -= self.max_drop_speed;
+                        if !self.infinite_hold {
+                            self.max -= self.max_drop_speed;
 
-                        if self.max < 0.0 {
-                            self.max = 0.0;
+                            if self.max < 0.0 {
+                                self.max = 0.0;
+                            }
                         }
                     } else {
                         self.max_delay_ticker -= 1;
                     }
 
+                    // The blinking follows the updates, like the hold time of the max peak
+                    if self.silent {
+                        self.silent_ticker = (self.silent_ticker + 1) % (2 * SILENT_BLINK_TICKS);
+                    }
+
+                    self.update_readout(cx);
                     cx.style.needs_redraw = true;
                 }
                 MeterEvents::ChangeMeterScale(scale) => {
                     self.scale = *scale;
+
+                    // Keep the sections at the same levels
+                    if let Some(level_sections) = &self.level_sections {
+                        self.sections = self.scale.map_sections(level_sections);
+                    }
                 }
                 MeterEvents::ChangePeakDropSpeed(n) => {
                     self.max_drop_speed = *n;
@@ -193,13 +524,105 @@ impl View for Meter {
                     self.max_hold_time = *n;
                 }
                 MeterEvents::ChangeBarColor(col) => {
-                    self.bar_color = *col;
+                    self.bar_color = Some(*col);
                 }
                 MeterEvents::ChangeLineColor(col) => {
-                    self.line_color = *col;
+                    self.line_color = Some(*col);
                 }
                 MeterEvents::ChangeSections(sec) => {
                     self.sections = (*sec).to_owned();
+                    self.level_sections = None;
+                    self.custom_sections = true;
+                }
+                MeterEvents::ChangeLevelSections(sec) => {
+                    self.sections = self.scale.map_sections(sec);
+                    self.level_sections = Some((*sec).to_owned());
+                    self.custom_sections = true;
+                }
+                MeterEvents::ChangeArcAngles(start, end) => {
+                    self.arc_start = *start;
+                    self.arc_end = *end;
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::ChangeArcThickness(n) => {
+                    self.arc_thickness = *n;
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::ChangeSegments(segments) => {
+                    self.segments = segments.clone();
+                }
+                MeterEvents::ChangeSegmentGap(n) => {
+                    self.segment_gap = *n;
+                }
+                MeterEvents::ChangeUnlitSections(sec) => {
+                    self.unlit_sections = (*sec).to_owned();
+                }
+                MeterEvents::ChangeFillMode(mode) => {
+                    self.fill_mode = *mode;
+                }
+                MeterEvents::ChangeTrackColor(col) => {
+                    self.track_color = Some(*col);
+                }
+                MeterEvents::ChangeDimmedTrack(dimmed) => {
+                    self.dimmed_track = *dimmed;
+                }
+                MeterEvents::ChangeReadout(position) => {
+                    self.readout = *position;
+                    self.layout_readout(cx);
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::UpdateReadoutLevel(level) => {
+                    if self.readout_level.is_none() {
+                        if let Some(readout) = self.readout_parts {
+                            cx.style.display.insert(readout.level, Display::Flex);
+                            cx.style.needs_relayout = true;
+                        }
+                    }
+                    self.readout_level = Some(*level);
+                    self.update_readout(cx);
+                }
+                MeterEvents::ChangeInfiniteHold(hold) => {
+                    self.infinite_hold = *hold;
+                }
+                MeterEvents::ChangeMaxMarker(marker) => {
+                    self.max_marker = *marker;
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::ChangeResetOnClick(reset) => {
+                    self.reset_on_click = *reset;
+                }
+                MeterEvents::ChangeSilent(silent) => {
+                    self.silent = *silent;
+                    // Start lit, so the state shows up right away
+                    self.silent_ticker = 0;
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::ResetHold => {
+                    self.max = self.pos;
+                    self.max_delay_ticker = 0;
+                    self.all_time_max = self.pos;
+                    self.clipped = false;
+                    self.update_readout(cx);
+                    cx.style.needs_redraw = true;
+                }
+                MeterEvents::SetParts(parts) => {
+                    self.parts = Some(*parts);
+                }
+                MeterEvents::SetReadout(readout) => {
+                    self.readout_parts = Some(*readout);
+                    self.layout_readout(cx);
+                }
+            }
+        });
+
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Left) = window_event {
+                // Only clicks on the readout reset the hold, unless the whole meter is clickable
+                let on_readout = self.readout_parts.is_some_and(|readout| {
+                    [readout.container, readout.peak, readout.level].contains(&meta.target)
+                });
+                if self.reset_on_click || on_readout {
+                    cx.emit(MeterEvents::ResetHold);
                 }
             }
         });
@@ -208,55 +631,131 @@ impl View for Meter {
     fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
         let entity = cx.current();
 
-        let bounds = cx.cache().get_bounds(entity);
+        let outer_bounds = cx.cache().get_bounds(entity);
 
         //Skip meters with no width or no height
-        if bounds.w == 0.0 || bounds.h == 0.0 {
+        if outer_bounds.w == 0.0 || outer_bounds.h == 0.0 {
             return;
         }
-        let width = bounds.w;
-        let height = bounds.h;
-
-        // TODO:
-        // Whole meter background
-        // Border
-        // Border shape
-        // Padding
-        // Space
-
-        let pos_x = cx.cache().get_posx(entity);
-        let pos_y = cx.cache().get_posy(entity);
-        let value = self.pos;
-        let max = self.max;
 
         let opacity = cx.cache().get_opacity(entity);
 
-        let mut bar_color: Color = self.bar_color.into();
-        bar_color.set_alphaf(bar_color.a * opacity);
-
-        let mut line_color: Color = self.line_color.into();
-        line_color.set_alphaf(line_color.a * opacity);
-
         // Calculate the border radiuses
         // This is taken from the default draw implementation of Views
         let border_radius_top_left = cx
             .border_radius_top_left(entity)
             .unwrap_or_default()
-            .value_or(bounds.w.min(bounds.h), 0.0);
+            .value_or(outer_bounds.w.min(outer_bounds.h), 0.0);
 
         let border_radius_top_right = cx
             .border_radius_top_right(entity)
             .unwrap_or_default()
-            .value_or(bounds.w.min(bounds.h), 0.0);
+            .value_or(outer_bounds.w.min(outer_bounds.h), 0.0);
 
         let border_radius_bottom_left = cx
             .border_radius_bottom_left(entity)
             .unwrap_or_default()
-            .value_or(bounds.w.min(bounds.h), 0.0);
+            .value_or(outer_bounds.w.min(outer_bounds.h), 0.0);
         let border_radius_bottom_right = cx
             .border_radius_bottom_right(entity)
             .unwrap_or_default()
-            .value_or(bounds.w.min(bounds.h), 0.0);
+            .value_or(outer_bounds.w.min(outer_bounds.h), 0.0);
+
+        let border_width = cx
+            .border_width(entity)
+            .unwrap_or_default()
+            .value_or(outer_bounds.w.min(outer_bounds.h), 0.0);
+
+        // Draw the background of the whole meter and its border
+        // The border is stroked on the inside of the bounds, so it doesn't overlap neighbours
+        let mut outer_path = Path::new();
+        outer_path.rounded_rect_varying(
+            outer_bounds.x + border_width / 2.0,
+            outer_bounds.y + border_width / 2.0,
+            outer_bounds.w - border_width,
+            outer_bounds.h - border_width,
+            border_radius_top_left,
+            border_radius_top_right,
+            border_radius_bottom_left,
+            border_radius_bottom_right,
+        );
+
+        if let Some(background_color) = cx.background_color(entity).cloned() {
+            let mut background_color: Color = background_color.into();
+            background_color.set_alphaf(background_color.a * opacity);
+            canvas.fill_path(&mut outer_path, Paint::color(background_color));
+        }
+
+        if border_width > 0.0 {
+            if let Some(border_color) = cx.border_color(entity).cloned() {
+                let mut border_color: Color = border_color.into();
+                border_color.set_alphaf(border_color.a * opacity);
+
+                let mut border_paint = Paint::color(border_color);
+                border_paint.set_line_width(border_width);
+                canvas.stroke_path(&mut outer_path, border_paint);
+            }
+        }
+
+        // Everything else is drawn inside of the border and the padding (child space)
+        let padding = |units: Option<Units>, length: f32| {
+            units.unwrap_or_default().value_or(length, 0.0) + border_width
+        };
+        let left = padding(cx.child_left(entity), outer_bounds.w);
+        let right = padding(cx.child_right(entity), outer_bounds.w);
+        let top = padding(cx.child_top(entity), outer_bounds.h);
+        let bottom = padding(cx.child_bottom(entity), outer_bounds.h);
+
+        let bounds = BoundingBox {
+            x: outer_bounds.x + left,
+            y: outer_bounds.y + top,
+            w: outer_bounds.w - left - right,
+            h: outer_bounds.h - top - bottom,
+        };
+
+        // The header readout takes up a strip at the top of the meter
+        let content_bounds = bounds;
+        let bounds = if self.readout == Some(ReadoutPosition::Header) {
+            BoundingBox {
+                y: bounds.y + READOUT_HEIGHT,
+                h: bounds.h - READOUT_HEIGHT,
+                ..bounds
+            }
+        } else {
+            bounds
+        };
+
+        //Skip meters where the padding leaves no space for the bar
+        if bounds.w <= 0.0 || bounds.h <= 0.0 {
+            return;
+        }
+
+        let width = bounds.w;
+        let height = bounds.h;
+
+        let pos_x = bounds.x;
+        let pos_y = bounds.y;
+        let value = self.pos;
+        let max = self.max;
+
+        let style = self.resolve_style(cx, bounds);
+
+        let mut line_color: Color = style.line.into();
+        line_color.set_alphaf(line_color.a * opacity);
+
+        if self.segments.is_some() {
+            self.draw_segments(canvas, bounds, opacity, &style);
+            self.draw_max_marker(canvas, bounds, opacity, &style);
+            self.draw_clip_indicator(canvas, content_bounds, opacity, &style);
+            return;
+        }
+
+        if self.direction == Direction::Radial {
+            self.draw_radial(canvas, bounds, opacity, &style);
+            self.draw_max_marker(canvas, bounds, opacity, &style);
+            self.draw_clip_indicator(canvas, content_bounds, opacity, &style);
+            return;
+        }
 
         // Create variables for the rectangle
         let bar_x;
@@ -353,8 +852,53 @@ impl View for Meter {
                 grad_y_start = pos_y;
                 grad_y_end = pos_y;
             }
+            Direction::Radial => unreachable!("Radial meters are drawn by draw_radial"),
         };
 
+        // Draw the track behind the bar
+        let mut track_path = Path::new();
+        track_path.rounded_rect_varying(
+            pos_x,
+            pos_y,
+            width,
+            height,
+            border_radius_top_left,
+            border_radius_top_right,
+            border_radius_bottom_left,
+            border_radius_bottom_right,
+        );
+
+        if self.silent_lit() {
+            let mut silent_color: Color = style.silent.into();
+            silent_color.set_alphaf(silent_color.a * opacity);
+
+            canvas.fill_path(&mut track_path, Paint::color(silent_color));
+        } else if self.dimmed_track {
+            let femtovg_sections: Vec<(f32, vizia::vg::Color)> = style
+                .sections
+                .iter()
+                .flat_map(|(start, stop, col)| {
+                    let mut color: Color = dim(*col).into();
+                    color.set_alphaf(color.a * opacity);
+                    [(*start, color), (*stop, color)]
+                })
+                .collect();
+
+            let track_paint = Paint::linear_gradient_stops(
+                grad_x_start,
+                grad_y_start,
+                grad_x_end,
+                grad_y_end,
+                &femtovg_sections,
+            );
+            canvas.fill_path(&mut track_path, track_paint);
+        } else if let Some(track) = style.track {
+            let mut track_color: Color = track.into();
+            track_color.set_alphaf(track_color.a * opacity);
+
+            canvas.fill_path(&mut track_path, Paint::color(track_color));
+        }
+
         let mut bar_path = Path::new();
         bar_path.rounded_rect_varying(
             bar_x,
@@ -367,24 +911,52 @@ impl View for Meter {
             border_radius_bottom_right,
         );
 
-        // Convert our sections into a list femtovg can use
-        let mut femtovg_sections: Vec<(f32, vizia::vg::Color)> = Vec::new();
+        match self.fill_mode {
+            FillMode::Gradient => {
+                // Convert our sections into a list femtovg can use
+                let mut femtovg_sections: Vec<(f32, vizia::vg::Color)> = Vec::new();
 
-        for (start, stop, col) in &self.sections {
-            femtovg_sections.push((*start, (*col).into()));
-            femtovg_sections.push((*stop, (*col).into()));
-        }
+                for (start, stop, col) in &style.sections {
+                    femtovg_sections.push((*start, (*col).into()));
+                    femtovg_sections.push((*stop, (*col).into()));
+                }
 
-        // Draw the gradient
-        let mut bar_paint = Paint::linear_gradient_stops(
-            grad_x_start,
-            grad_y_start,
-            grad_x_end,
-            grad_y_end,
-            &femtovg_sections,
-        );
+                // Draw the gradient
+                let mut bar_paint = Paint::linear_gradient_stops(
+                    grad_x_start,
+                    grad_y_start,
+                    grad_x_end,
+                    grad_y_end,
+                    &femtovg_sections,
+                );
 
-        canvas.fill_path(&mut bar_path, bar_paint);
+                canvas.fill_path(&mut bar_path, bar_paint);
+            }
+            FillMode::Zones => {
+                // Every zone is clipped to the bar, so only the reached zones are drawn.
+                // These are plain rectangles, so the border radius isn't applied.
+                for (start, stop, col) in &style.sections {
+                    let stop = stop.min(value);
+                    if stop <= *start {
+                        continue;
+                    }
+
+                    let mut color: Color = (*col).into();
+                    color.set_alphaf(color.a * opacity);
+
+                    let (x, y, w, h) = self.range_rect(bounds, *start, stop);
+                    let mut zone_path = Path::new();
+                    zone_path.rect(x, y, w, h);
+                    canvas.fill_path(&mut zone_path, Paint::color(color));
+                }
+            }
+            FillMode::Level => {
+                let mut color: Color = self.level_color(&style).into();
+                color.set_alphaf(color.a * opacity);
+
+                canvas.fill_path(&mut bar_path, Paint::color(color));
+            }
+        }
 
         // Draw the peak line
         let mut line_path = Path::new();
@@ -392,12 +964,597 @@ impl View for Meter {
         line_path.line_to(line_x2, line_y2);
 
         let mut line_paint = Paint::color(line_color);
-        line_paint.set_line_width(2.0);
+        line_paint.set_line_width(style.line_width);
+
+        canvas.stroke_path(&mut line_path, line_paint);
 
-        canvas.stroke_path(&mut line_path, line_paint)
+        self.draw_max_marker(canvas, bounds, opacity, &style);
+        self.draw_clip_indicator(canvas, content_bounds, opacity, &style);
     }
 }
 
+impl Meter {
+    /// Draw the meter as an arc inside the given bounds.
+    ///
+    /// The sections are drawn as separate arc segments, and the gradient as many short ones,
+    /// since femtovg doesn't have a gradient that follows an arc.
+    fn draw_radial(
+        &self,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        opacity: f32,
+        style: &ResolvedStyle,
+    ) {
+        let (centre_x, centre_y, radius) = match self.arc_geometry(bounds) {
+            Some(geometry) => geometry,
+            None => return,
+        };
+        let angle_at = |pos: f32| self.arc_angle(pos);
+
+        // A single section covering the whole arc colours it by the current level
+        let level_section = [(0.0, 1.0, self.level_color(style))];
+        let gradient_sections;
+        let sections: &[(f32, f32, vizia::Color)] = match self.fill_mode {
+            FillMode::Level => &level_section,
+            FillMode::Zones => &style.sections,
+            FillMode::Gradient => {
+                gradient_sections = self.arc_gradient(&style.sections);
+                &gradient_sections
+            }
+        };
+
+        // Draw the track as the whole arc behind the bar
+        let track_sections: Vec<(f32, f32, vizia::Color)> = if self.silent_lit() {
+            vec![(0.0, 1.0, style.silent)]
+        } else if self.dimmed_track {
+            sections
+                .iter()
+                .map(|(start, stop, col)| (*start, *stop, dim(*col)))
+                .collect()
+        } else {
+            style.track.map(|col| (0.0, 1.0, col)).into_iter().collect()
+        };
+
+        for (start, stop, col) in track_sections {
+            let mut track_color: Color = col.into();
+            track_color.set_alphaf(track_color.a * opacity);
+
+            let mut track_path = Path::new();
+            track_path.arc(
+                centre_x,
+                centre_y,
+                radius,
+                angle_at(start),
+                angle_at(stop),
+                self.arc_winding(),
+            );
+
+            let mut track_paint = Paint::color(track_color);
+            track_paint.set_line_width(self.arc_thickness);
+            track_paint.set_line_cap(LineCap::Butt);
+            canvas.stroke_path(&mut track_path, track_paint);
+        }
+
+        for (start, stop, col) in sections {
+            let stop = stop.min(self.pos);
+            if stop <= *start {
+                continue;
+            }
+
+            let mut color: Color = (*col).into();
+            color.set_alphaf(color.a * opacity);
+
+            let mut path = Path::new();
+            path.arc(
+                centre_x,
+                centre_y,
+                radius,
+                angle_at(*start),
+                angle_at(stop),
+                self.arc_winding(),
+            );
+
+            let mut paint = Paint::color(color);
+            paint.set_line_width(self.arc_thickness);
+            paint.set_line_cap(LineCap::Butt);
+            canvas.stroke_path(&mut path, paint);
+        }
+
+        // Draw the peak as a tick across the arc
+        let angle = angle_at(self.max);
+        let inner = radius - self.arc_thickness / 2.0;
+        let outer = radius + self.arc_thickness / 2.0;
+
+        let mut line_path = Path::new();
+        line_path.move_to(
+            centre_x + inner * angle.cos(),
+            centre_y + inner * angle.sin(),
+        );
+        line_path.line_to(
+            centre_x + outer * angle.cos(),
+            centre_y + outer * angle.sin(),
+        );
+
+        let mut line_color: Color = style.line.into();
+        line_color.set_alphaf(line_color.a * opacity);
+
+        let mut line_paint = Paint::color(line_color);
+        line_paint.set_line_width(style.line_width);
+
+        canvas.stroke_path(&mut line_path, line_paint);
+    }
+
+    /// Draw the marker of the all-time maximum as a line across the meter, if it is enabled
+    fn draw_max_marker(
+        &self,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        opacity: f32,
+        style: &ResolvedStyle,
+    ) {
+        if !self.max_marker {
+            return;
+        }
+
+        let mut marker_path = Path::new();
+        if self.direction == Direction::Radial {
+            let (centre_x, centre_y, radius) = match self.arc_geometry(bounds) {
+                Some(geometry) => geometry,
+                None => return,
+            };
+
+            let angle = self.arc_angle(self.all_time_max);
+            let inner = radius - self.arc_thickness / 2.0;
+            let outer = radius + self.arc_thickness / 2.0;
+
+            marker_path.move_to(
+                centre_x + inner * angle.cos(),
+                centre_y + inner * angle.sin(),
+            );
+            marker_path.line_to(
+                centre_x + outer * angle.cos(),
+                centre_y + outer * angle.sin(),
+            );
+        } else {
+            // An empty range is a line across the meter
+            let (x, y, w, h) = self.range_rect(bounds, self.all_time_max, self.all_time_max);
+            marker_path.move_to(x, y);
+            marker_path.line_to(x + w, y + h);
+        }
+
+        let mut marker_color: Color = style.marker.into();
+        marker_color.set_alphaf(marker_color.a * opacity);
+
+        let mut marker_paint = Paint::color(marker_color);
+        marker_paint.set_line_width(style.line_width);
+
+        canvas.stroke_path(&mut marker_path, marker_paint);
+    }
+
+    /// Light up the area of the readout in the clip colour once the input reached full scale.
+    /// The area matches the placement of `layout_readout` inside the border and padding.
+    fn draw_clip_indicator(
+        &self,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        opacity: f32,
+        style: &ResolvedStyle,
+    ) {
+        let position = match self.readout {
+            Some(position) if self.clipped => position,
+            _ => return,
+        };
+
+        let (x, y, w, h) = match (position, self.direction) {
+            (ReadoutPosition::End, Direction::Right) => (
+                bounds.x + bounds.w - READOUT_WIDTH,
+                bounds.y,
+                READOUT_WIDTH,
+                bounds.h,
+            ),
+            (ReadoutPosition::End, Direction::Left) => {
+                (bounds.x, bounds.y, READOUT_WIDTH, bounds.h)
+            }
+            (ReadoutPosition::End, Direction::Down) => (
+                bounds.x,
+                bounds.y + bounds.h - READOUT_HEIGHT,
+                bounds.w,
+                READOUT_HEIGHT,
+            ),
+            (ReadoutPosition::End, Direction::Radial) => (
+                bounds.x,
+                bounds.y + (bounds.h - READOUT_HEIGHT) / 2.0,
+                bounds.w,
+                READOUT_HEIGHT,
+            ),
+            (ReadoutPosition::Header, _) | (ReadoutPosition::End, Direction::Up) => {
+                (bounds.x, bounds.y, bounds.w, READOUT_HEIGHT)
+            }
+        };
+
+        let mut clip_color: Color = style.clip.into();
+        clip_color.set_alphaf(clip_color.a * opacity);
+
+        let mut clip_path = Path::new();
+        clip_path.rect(x, y, w.min(bounds.w), h.min(bounds.h));
+        canvas.fill_path(&mut clip_path, Paint::color(clip_color));
+    }
+
+    /// Update the text of the readout labels from the held peak and the readout level
+    fn update_readout(&self, cx: &mut Context) {
+        let readout = match (self.readout, self.readout_parts) {
+            (Some(_), Some(readout)) => readout,
+            _ => return,
+        };
+
+        let peak = if self.clipped {
+            "OVER".to_string()
+        } else {
+            format_level(lin2db(self.scale.unmap(self.max)))
+        };
+        cx.style.text.insert(readout.peak, peak);
+
+        if let Some(level) = self.readout_level {
+            cx.style.text.insert(readout.level, format_level(level));
+        }
+    }
+
+    /// Place the readout container for the readout position and the direction of the meter
+    fn layout_readout(&self, cx: &mut Context) {
+        let readout = match self.readout_parts {
+            Some(readout) => readout,
+            None => return,
+        };
+
+        let position = match self.readout {
+            Some(position) => position,
+            None => {
+                cx.style.display.insert(readout.container, Display::None);
+                cx.style.needs_relayout = true;
+                return;
+            }
+        };
+
+        // The readout spans the width of the meter, except at the end of a horizontal meter
+        // (left, right, top, bottom, width, height)
+        let (left, right, top, bottom, width, height) = match (position, self.direction) {
+            (ReadoutPosition::End, Direction::Right) => (
+                Stretch(1.0),
+                Pixels(0.0),
+                Pixels(0.0),
+                Pixels(0.0),
+                Pixels(READOUT_WIDTH),
+                Stretch(1.0),
+            ),
+            (ReadoutPosition::End, Direction::Left) => (
+                Pixels(0.0),
+                Stretch(1.0),
+                Pixels(0.0),
+                Pixels(0.0),
+                Pixels(READOUT_WIDTH),
+                Stretch(1.0),
+            ),
+            (ReadoutPosition::End, Direction::Down) => (
+                Pixels(0.0),
+                Pixels(0.0),
+                Stretch(1.0),
+                Pixels(0.0),
+                Stretch(1.0),
+                Pixels(READOUT_HEIGHT),
+            ),
+            // Centred inside of the arc
+            (ReadoutPosition::End, Direction::Radial) => (
+                Pixels(0.0),
+                Pixels(0.0),
+                Stretch(1.0),
+                Stretch(1.0),
+                Stretch(1.0),
+                Pixels(READOUT_HEIGHT),
+            ),
+            (ReadoutPosition::Header, _) | (ReadoutPosition::End, Direction::Up) => (
+                Pixels(0.0),
+                Pixels(0.0),
+                Pixels(0.0),
+                Stretch(1.0),
+                Stretch(1.0),
+                Pixels(READOUT_HEIGHT),
+            ),
+        };
+
+        cx.style.left.insert(readout.container, left);
+        cx.style.right.insert(readout.container, right);
+        cx.style.top.insert(readout.container, top);
+        cx.style.bottom.insert(readout.container, bottom);
+        cx.style.width.insert(readout.container, width);
+        cx.style.height.insert(readout.container, height);
+        cx.style.display.insert(readout.container, Display::Flex);
+        cx.style.needs_relayout = true;
+
+        self.update_readout(cx);
+    }
+
+    /// Combine the values set using the handles with the style of the hidden parts
+    fn resolve_style(&self, cx: &DrawContext<'_>, bounds: BoundingBox) -> ResolvedStyle {
+        let color_of =
+            |part: Option<Entity>| part.and_then(|part| cx.background_color(part).cloned());
+
+        let parts = self.parts;
+
+        let mut sections = self.sections.clone();
+        if !self.custom_sections {
+            if let Some(parts) = parts {
+                for ((_, _, col), part) in sections.iter_mut().zip(parts.sections) {
+                    if let Some(style_color) = color_of(Some(part)) {
+                        *col = style_color;
+                    }
+                }
+            }
+        }
+
+        let line_width = parts
+            .and_then(|parts| cx.border_width(parts.peak))
+            .map(|width| width.value_or(bounds.w.min(bounds.h), 2.0))
+            .unwrap_or(2.0);
+
+        ResolvedStyle {
+            track: self
+                .track_color
+                .or_else(|| color_of(parts.map(|parts| parts.track))),
+            bar: self
+                .bar_color
+                .or_else(|| color_of(parts.map(|parts| parts.bar)))
+                .unwrap_or_else(vizia::Color::red),
+            line: self
+                .line_color
+                .or_else(|| color_of(parts.map(|parts| parts.peak)))
+                .unwrap_or_else(vizia::Color::black),
+            line_width,
+            marker: color_of(parts.map(|parts| parts.max))
+                .unwrap_or_else(|| vizia::Color::rgb(64, 160, 255)),
+            silent: color_of(parts.map(|parts| parts.silent))
+                .unwrap_or_else(|| vizia::Color::rgb(128, 64, 192)),
+            clip: color_of(parts.map(|parts| parts.clip))
+                .unwrap_or_else(|| vizia::Color::rgb(160, 16, 16)),
+            sections,
+        }
+    }
+
+    /// Whether the track is lit in the silent colour at this point of the blinking
+    fn silent_lit(&self) -> bool {
+        self.silent && self.silent_ticker < SILENT_BLINK_TICKS
+    }
+
+    /// The colour of the section the current level is in.
+    /// Falls back to the bar colour if the level isn't in any section.
+    fn level_color(&self, style: &ResolvedStyle) -> vizia::Color {
+        section_color(&style.sections, self.pos).unwrap_or(style.bar)
+    }
+
+    /// The centre and radius of the arc of a radial meter, or `None` if it doesn't fit
+    fn arc_geometry(&self, bounds: BoundingBox) -> Option<(f32, f32, f32)> {
+        let radius = (bounds.w.min(bounds.h) - self.arc_thickness) / 2.0;
+
+        (radius > 0.0).then(|| (bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0, radius))
+    }
+
+    /// The angle in radians of a position on the arc of a radial meter
+    fn arc_angle(&self, pos: f32) -> f32 {
+        // Angles are stored clockwise from the top, femtovg measures them clockwise from the right
+        (self.arc_start + pos.clamp(0.0, 1.0) * (self.arc_end - self.arc_start) - 90.0).to_radians()
+    }
+
+    /// The sections turned into short arcs that follow their gradient.
+    /// femtovg can only blend colours along a straight line, so the arc is split up
+    /// and every piece takes the colour of the gradient at its middle.
+    fn arc_gradient(&self, sections: &[(f32, f32, vizia::Color)]) -> Vec<(f32, f32, vizia::Color)> {
+        let steps = ((self.arc_end - self.arc_start).abs() / ARC_GRADIENT_STEP)
+            .ceil()
+            .max(1.0) as usize;
+
+        (0..steps)
+            .map(|i| {
+                let start = i as f32 / steps as f32;
+                let stop = (i + 1) as f32 / steps as f32;
+                (start, stop, gradient_color(sections, (start + stop) / 2.0))
+            })
+            .collect()
+    }
+
+    /// The winding femtovg needs to draw the arc from the start to the end angle.
+    /// `Hole` winds clockwise, `Solid` counter-clockwise.
+    fn arc_winding(&self) -> Solidity {
+        if self.arc_end >= self.arc_start {
+            Solidity::Hole
+        } else {
+            Solidity::Solid
+        }
+    }
+
+    /// The positions in \[0,1\] where the segments start and end
+    fn segment_bounds(&self) -> Vec<f32> {
+        self.segments
+            .as_ref()
+            .map(|segments| segments.bounds(self.scale))
+            .unwrap_or_default()
+    }
+
+    /// The rectangle covering the positions from `start` to `stop` of a straight meter
+    fn range_rect(&self, bounds: BoundingBox, start: f32, stop: f32) -> (f32, f32, f32, f32) {
+        let length = stop - start;
+
+        match self.direction {
+            Direction::Up => (
+                bounds.x,
+                bounds.y + (1.0 - stop) * bounds.h,
+                bounds.w,
+                length * bounds.h,
+            ),
+            Direction::Down => (
+                bounds.x,
+                bounds.y + start * bounds.h,
+                bounds.w,
+                length * bounds.h,
+            ),
+            Direction::Right => (
+                bounds.x + start * bounds.w,
+                bounds.y,
+                length * bounds.w,
+                bounds.h,
+            ),
+            Direction::Left => (
+                bounds.x + (1.0 - stop) * bounds.w,
+                bounds.y,
+                length * bounds.w,
+                bounds.h,
+            ),
+            Direction::Radial => unreachable!("Radial meters don't have rectangular ranges"),
+        }
+    }
+
+    /// Draw the meter as discrete LED segments.
+    ///
+    /// A segment is lit as soon as the bar reaches into it,
+    /// and the segment containing the max peak stays lit to show the peak.
+    fn draw_segments(
+        &self,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        opacity: f32,
+        style: &ResolvedStyle,
+    ) {
+        let arc = match self.direction {
+            Direction::Radial => match self.arc_geometry(bounds) {
+                Some(geometry) => Some(geometry),
+                None => return,
+            },
+            _ => None,
+        };
+
+        let segment_bounds = self.segment_bounds();
+
+        for segment in segment_bounds.windows(2) {
+            let (start, stop) = (segment[0], segment[1]);
+            let centre = (start + stop) / 2.0;
+
+            let lit = self.pos > start || (self.max > start && self.max <= stop);
+            let lit_color = match self.fill_mode {
+                FillMode::Level => self.level_color(style),
+                FillMode::Gradient | FillMode::Zones => {
+                    section_color(&style.sections, centre).unwrap_or(style.bar)
+                }
+            };
+
+            let mut color: Color = if lit {
+                lit_color.into()
+            } else if self.silent_lit() {
+                style.silent.into()
+            } else {
+                match section_color(&self.unlit_sections, centre) {
+                    Some(col) => col.into(),
+                    None => dim(lit_color).into(),
+                }
+            };
+            color.set_alphaf(color.a * opacity);
+
+            let mut path = Path::new();
+            let mut paint = Paint::color(color);
+
+            match arc {
+                Some((centre_x, centre_y, radius)) => {
+                    // The gap is in pixels along the arc, so convert it to an angle
+                    let gap = self.segment_gap / radius / 2.0;
+                    let (mut a0, mut a1) = (self.arc_angle(start), self.arc_angle(stop));
+                    if a1 > a0 {
+                        a0 += gap;
+                        a1 -= gap;
+                    } else {
+                        a0 -= gap;
+                        a1 += gap;
+                    }
+
+                    path.arc(centre_x, centre_y, radius, a0, a1, self.arc_winding());
+                    paint.set_line_width(self.arc_thickness);
+                    paint.set_line_cap(LineCap::Butt);
+                    canvas.stroke_path(&mut path, paint);
+                }
+                None => {
+                    let (x, y, w, h) = self.range_rect(bounds, start, stop);
+                    let half_gap = self.segment_gap / 2.0;
+
+                    match self.direction {
+                        Direction::Up | Direction::Down => {
+                            path.rect(x, y + half_gap, w, (h - self.segment_gap).max(0.0))
+                        }
+                        _ => path.rect(x + half_gap, y, (w - self.segment_gap).max(0.0), h),
+                    }
+                    canvas.fill_path(&mut path, paint);
+                }
+            }
+        }
+    }
+}
+
+/// Move a position towards the target by the smoothing factor in (0,1\],
+/// the step a meter takes on every update
+pub fn smooth(pos: f32, target: f32, smoothing_factor: f32) -> f32 {
+    pos - smoothing_factor * (pos - target)
+}
+
+/// Format a level in dB for the readout with one decimal
+fn format_level(level: f32) -> String {
+    if level < READOUT_FLOOR {
+        "-inf".to_string()
+    } else {
+        format!("{:.1}", level)
+    }
+}
+
+/// A darker version of a colour, used for unlit segments and the dimmed track
+fn dim(col: vizia::Color) -> vizia::Color {
+    vizia::Color::rgba(col.r() / 4, col.g() / 4, col.b() / 4, col.a())
+}
+
+/// The colour of the section the position is in
+fn section_color(sections: &[(f32, f32, vizia::Color)], pos: f32) -> Option<vizia::Color> {
+    sections
+        .iter()
+        .find(|(start, stop, _)| *start <= pos && pos <= *stop)
+        .map(|(_, _, col)| *col)
+}
+
+/// The colour at the position of the gradient the sections form.
+/// Like the gradient of the bar, every section is a pair of stops with its colour,
+/// and the colours are blended linearly between the stops.
+fn gradient_color(sections: &[(f32, f32, vizia::Color)], pos: f32) -> vizia::Color {
+    let stops: Vec<(f32, vizia::Color)> = sections
+        .iter()
+        .flat_map(|(start, stop, col)| [(*start, *col), (*stop, *col)])
+        .collect();
+
+    let next = match stops.iter().position(|(stop, _)| *stop >= pos) {
+        Some(0) => return stops[0].1,
+        Some(next) => next,
+        None => {
+            return stops
+                .last()
+                .map(|(_, col)| *col)
+                .unwrap_or_else(|| vizia::Color::rgba(0, 0, 0, 0))
+        }
+    };
+
+    let (start, from) = stops[next - 1];
+    let (stop, to) = stops[next];
+    let t = ((pos - start) / (stop - start)).clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+    vizia::Color::rgba(
+        mix(from.r(), to.r()),
+        mix(from.g(), to.g()),
+        mix(from.b(), to.b()),
+        mix(from.a(), to.a()),
+    )
+}
+
 pub trait MeterHandle {
     fn peak_drop_speed(self, val: impl Res<f32>) -> Self;
     fn smoothing_factor(self, val: impl Res<f32>) -> Self;
@@ -406,6 +1563,24 @@ pub trait MeterHandle {
     fn line_color(self, val: impl Res<vizia::Color>) -> Self;
     fn scale(self, val: impl Res<MeterScale>) -> Self;
     fn sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>) -> Self;
+    fn level_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>) -> Self;
+    fn loudness_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>, target: f32) -> Self;
+    fn arc_angles(self, start: f32, end: f32) -> Self;
+    fn arc_thickness(self, val: impl Res<f32>) -> Self;
+    fn segments(self, count: usize) -> Self;
+    fn segment_levels(self, levels: Vec<f32>) -> Self;
+    fn segment_gap(self, val: impl Res<f32>) -> Self;
+    fn unlit_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>) -> Self;
+    fn fill_mode(self, val: impl Res<FillMode>) -> Self;
+    fn track_color(self, val: impl Res<vizia::Color>) -> Self;
+    fn dimmed_track(self, val: impl Res<bool>) -> Self;
+    fn readout(self, position: ReadoutPosition) -> Self;
+    fn readout_level(self, val: impl Res<f32>) -> Self;
+    fn infinite_hold(self, val: impl Res<bool>) -> Self;
+    fn max_marker(self, val: impl Res<bool>) -> Self;
+    fn reset_on_click(self, val: impl Res<bool>) -> Self;
+    fn reset(self, val: impl Res<u32>) -> Self;
+    fn silent(self, val: impl Res<bool>) -> Self;
 }
 
 impl MeterHandle for Handle<'_, Meter> {
@@ -464,4 +1639,262 @@ impl MeterHandle for Handle<'_, Meter> {
 
         self
     }
+
+    fn level_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeLevelSections(value));
+        });
+
+        self
+    }
+
+    /// Set the sections in LU relative to the target loudness in LUFS, for a meter fed with
+    /// a loudness like `db2lin(momentary)`. E.g. with a target of -23, a section from
+    /// -1 to 1 LU covers -24 to -22 LUFS.
+    fn loudness_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>, target: f32) -> Self {
+        val.set_or_bind(self.cx, self.entity, move |cx, entity, value| {
+            let sections = value
+                .into_iter()
+                .map(|(start, stop, col)| (target + start, target + stop, col))
+                .collect();
+            entity.emit(cx, MeterEvents::ChangeLevelSections(sections));
+        });
+
+        self
+    }
+
+    fn arc_angles(self, start: f32, end: f32) -> Self {
+        self.entity
+            .emit(self.cx, MeterEvents::ChangeArcAngles(start, end));
+
+        self
+    }
+
+    fn arc_thickness(self, val: impl Res<f32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeArcThickness(value));
+        });
+
+        self
+    }
+
+    fn segments(self, count: usize) -> Self {
+        self.entity.emit(
+            self.cx,
+            MeterEvents::ChangeSegments(Some(SegmentLayout::Count(count))),
+        );
+
+        self
+    }
+
+    fn segment_levels(self, levels: Vec<f32>) -> Self {
+        self.entity.emit(
+            self.cx,
+            MeterEvents::ChangeSegments(Some(SegmentLayout::Levels(levels))),
+        );
+
+        self
+    }
+
+    fn segment_gap(self, val: impl Res<f32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeSegmentGap(value));
+        });
+
+        self
+    }
+
+    fn unlit_sections(self, val: impl Res<Vec<(f32, f32, vizia::Color)>>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeUnlitSections(value));
+        });
+
+        self
+    }
+
+    fn fill_mode(self, val: impl Res<FillMode>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeFillMode(value));
+        });
+
+        self
+    }
+
+    fn track_color(self, val: impl Res<vizia::Color>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeTrackColor(value));
+        });
+
+        self
+    }
+
+    fn dimmed_track(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeDimmedTrack(value));
+        });
+
+        self
+    }
+
+    fn readout(self, position: ReadoutPosition) -> Self {
+        self.entity
+            .emit(self.cx, MeterEvents::ChangeReadout(Some(position)));
+
+        self
+    }
+
+    fn readout_level(self, val: impl Res<f32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::UpdateReadoutLevel(value));
+        });
+
+        self
+    }
+
+    fn infinite_hold(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeInfiniteHold(value));
+        });
+
+        self
+    }
+
+    fn max_marker(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeMaxMarker(value));
+        });
+
+        self
+    }
+
+    fn reset_on_click(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeResetOnClick(value));
+        });
+
+        self
+    }
+
+    fn reset(self, val: impl Res<u32>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, _| {
+            entity.emit(cx, MeterEvents::ResetHold);
+        });
+
+        self
+    }
+
+    fn silent(self, val: impl Res<bool>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, MeterEvents::ChangeSilent(value));
+        });
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red() -> vizia::Color {
+        vizia::Color::rgb(255, 0, 0)
+    }
+
+    fn green() -> vizia::Color {
+        vizia::Color::rgb(0, 255, 0)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn full_scale_is_the_end_of_every_scale() {
+        for scale in [MeterScale::Linear, MeterScale::Logarithmic] {
+            assert_close(scale.map_db(0.0), 1.0);
+            // Levels above full scale stay at the end of the meter
+            assert_close(scale.map_db(6.0), 1.0);
+            assert_close(scale.map_db(f32::NEG_INFINITY), 0.0);
+        }
+    }
+
+    #[test]
+    fn positions_map_back_to_their_level() {
+        for scale in [MeterScale::Linear, MeterScale::Logarithmic] {
+            for level in [-60.0, -20.0, -3.0] {
+                assert_close(lin2db(scale.unmap(scale.map_db(level))), level);
+            }
+        }
+    }
+
+    #[test]
+    fn level_sections_keep_their_level_across_scales() {
+        let sections = [(-60.0, -3.0, green()), (-3.0, 0.0, red())];
+
+        let linear = MeterScale::Linear.map_sections(&sections);
+        let logarithmic = MeterScale::Logarithmic.map_sections(&sections);
+
+        // -3 dB is at about 71% of a linear meter, but at about 92% of a logarithmic one
+        assert_close(linear[1].0, db2lin(-3.0));
+        assert_close(logarithmic[1].0, db2lin(-3.0).powf(0.25));
+        assert!(logarithmic[1].0 > linear[1].0);
+
+        for (scale, mapped) in [
+            (MeterScale::Linear, &linear),
+            (MeterScale::Logarithmic, &logarithmic),
+        ] {
+            assert_close(lin2db(scale.unmap(mapped[0].1)), -3.0);
+            assert_close(lin2db(scale.unmap(mapped[1].0)), -3.0);
+            assert_close(mapped[1].1, 1.0);
+            assert_eq!(mapped[1].2, red());
+        }
+    }
+
+    #[test]
+    fn segments_by_count_are_evenly_spread() {
+        let bounds = SegmentLayout::Count(4).bounds(MeterScale::Logarithmic);
+        assert_eq!(bounds, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn segments_by_level_light_up_at_their_level() {
+        let levels = SegmentLayout::Levels(vec![-40.0, -20.0, -6.0]);
+
+        for scale in [MeterScale::Linear, MeterScale::Logarithmic] {
+            let bounds = levels.bounds(scale);
+            assert_eq!(bounds.len(), 4);
+            assert_close(bounds[1], scale.map_db(-20.0));
+            assert_close(bounds[3], 1.0);
+        }
+    }
+
+    #[test]
+    fn section_color_finds_the_section() {
+        let sections = [(0.0, 0.5, green()), (0.5, 0.8, red())];
+
+        assert_eq!(section_color(&sections, 0.25), Some(green()));
+        assert_eq!(section_color(&sections, 0.6), Some(red()));
+        assert_eq!(section_color(&sections, 0.9), None);
+    }
+
+    #[test]
+    fn gradient_color_blends_between_sections() {
+        let sections = [(0.0, 0.4, green()), (0.6, 1.0, red())];
+
+        assert_eq!(gradient_color(&sections, 0.2), green());
+        assert_eq!(
+            gradient_color(&sections, 0.45),
+            vizia::Color::rgb(64, 191, 0)
+        );
+        assert_eq!(gradient_color(&sections, 0.8), red());
+        assert_eq!(gradient_color(&[], 0.5), vizia::Color::rgba(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn levels_are_formatted_with_one_decimal() {
+        assert_eq!(format_level(-3.04), "-3.0");
+        assert_eq!(format_level(0.0), "0.0");
+        assert_eq!(format_level(-120.0), "-inf");
+        assert_eq!(format_level(f32::NEG_INFINITY), "-inf");
+    }
 }
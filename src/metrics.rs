@@ -0,0 +1,214 @@
+use crate::lin2db;
+use crate::measurement::{MeasurementFrame, MAX_CHANNELS};
+use std::fmt::Write;
+
+/// The length of the window the peak gauge covers in seconds
+const PEAK_WINDOW: f32 = 1.0;
+
+/// The state of the meter exposed in the Prometheus text format.
+///
+/// The metrics are updated with every frame from the measurement queue and rendered when
+/// they are scraped. The peak is the highest over the last complete window of about a second,
+/// so a scrape never misses a peak that happened between two scrapes of a fast scraper.
+pub struct Metrics {
+    sample_rate: f32,
+    /// The level in dBFS a channel has to exceed to not count as silent
+    silence_threshold: f32,
+    channel_count: usize,
+    /// The peak of the last complete window
+    peaks: [f32; MAX_CHANNELS],
+    /// The peak of the window that is being collected
+    window_peaks: [f32; MAX_CHANNELS],
+    /// The amount of samples in the window that is being collected
+    window_length: u64,
+    short_term: [f32; MAX_CHANNELS],
+    integrated: [f32; MAX_CHANNELS],
+    /// The amount of process cycles with a sample at or above full scale
+    clips: [u64; MAX_CHANNELS],
+    /// The position of the last sample above the silence threshold
+    last_sound: [u64; MAX_CHANNELS],
+    /// The position of the end of the last frame
+    position: u64,
+    dsp_load: f32,
+    xruns: u64,
+}
+
+impl Metrics {
+    /// The silence threshold is the one of the silence detection, so both agree on what is silent
+    pub fn new(sample_rate: f32, silence_threshold: f32) -> Self {
+        Self {
+            sample_rate,
+            silence_threshold,
+            channel_count: 0,
+            peaks: [0.0; MAX_CHANNELS],
+            window_peaks: [0.0; MAX_CHANNELS],
+            window_length: 0,
+            short_term: [f32::NEG_INFINITY; MAX_CHANNELS],
+            integrated: [f32::NEG_INFINITY; MAX_CHANNELS],
+            clips: [0; MAX_CHANNELS],
+            last_sound: [0; MAX_CHANNELS],
+            position: 0,
+            dsp_load: 0.0,
+            xruns: 0,
+        }
+    }
+
+    /// Add a frame from the measurement queue.
+    /// The inputs are exported in the Mid/Side mode too, so the labels keep their meaning.
+    pub fn update(&mut self, frame: &MeasurementFrame) {
+        let frame = frame.stereo_frame();
+        self.channel_count = frame.channel_count;
+        self.position = frame.position + frame.length as u64;
+        self.dsp_load = frame.dsp_load;
+        self.xruns = frame.xruns;
+
+        for (channel, measurement) in frame.channels().iter().enumerate() {
+            self.window_peaks[channel] = self.window_peaks[channel].max(measurement.peak);
+            self.short_term[channel] = measurement.short_term;
+            self.integrated[channel] = measurement.integrated;
+
+            if measurement.peak >= 1.0 {
+                self.clips[channel] += 1;
+            }
+            if lin2db(measurement.peak) > self.silence_threshold {
+                self.last_sound[channel] = self.position;
+            }
+        }
+
+        self.window_length += frame.length as u64;
+        if self.window_length as f32 >= PEAK_WINDOW * self.sample_rate {
+            self.peaks = self.window_peaks;
+            self.window_peaks = [0.0; MAX_CHANNELS];
+            self.window_length = 0;
+        }
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+
+        self.gauge(
+            &mut text,
+            "jack_meter_peak_dbfs",
+            "The sample peak over the last second in dBFS",
+            |c| lin2db(self.peaks[c]),
+        );
+        self.gauge(
+            &mut text,
+            "jack_meter_short_term_lufs",
+            "The short-term loudness in LUFS",
+            |c| self.short_term[c],
+        );
+        self.gauge(
+            &mut text,
+            "jack_meter_integrated_lufs",
+            "The integrated loudness in LUFS",
+            |c| self.integrated[c],
+        );
+        let silence_help = format!(
+            "The time since the channel last exceeded {} dBFS in seconds",
+            self.silence_threshold
+        );
+        self.gauge(
+            &mut text,
+            "jack_meter_silence_seconds",
+            &silence_help,
+            |c| (self.position - self.last_sound[c]) as f32 / self.sample_rate,
+        );
+
+        let _ = writeln!(
+            text,
+            "# HELP jack_meter_clips_total The amount of process cycles that reached full scale"
+        );
+        let _ = writeln!(text, "# TYPE jack_meter_clips_total counter");
+        for channel in 0..self.channel_count {
+            let _ = writeln!(
+                text,
+                "jack_meter_clips_total{{channel=\"{}\"}} {}",
+                channel + 1,
+                self.clips[channel]
+            );
+        }
+
+        let _ = writeln!(
+            text,
+            "# HELP jack_meter_xruns_total The amount of xruns since the client was activated"
+        );
+        let _ = writeln!(text, "# TYPE jack_meter_xruns_total counter");
+        let _ = writeln!(text, "jack_meter_xruns_total {}", self.xruns);
+        let _ = writeln!(
+            text,
+            "# HELP jack_meter_dsp_load_percent The load of the JACK engine in percent"
+        );
+        let _ = writeln!(text, "# TYPE jack_meter_dsp_load_percent gauge");
+        let _ = writeln!(
+            text,
+            "jack_meter_dsp_load_percent {}",
+            prometheus_number(self.dsp_load)
+        );
+
+        text
+    }
+
+    /// Write a gauge with a value per channel
+    fn gauge(&self, text: &mut String, name: &str, help: &str, value: impl Fn(usize) -> f32) {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} gauge", name);
+        for channel in 0..self.channel_count {
+            let _ = writeln!(
+                text,
+                "{}{{channel=\"{}\"}} {}",
+                name,
+                channel + 1,
+                prometheus_number(value(channel))
+            );
+        }
+    }
+}
+
+/// A number in the exposition format, which spells out infinity unlike Rust
+fn prometheus_number(value: f32) -> String {
+    if value == f32::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if value == f32::INFINITY {
+        "+Inf".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_uses_the_configured_threshold() {
+        let mut metrics = Metrics::new(48000.0, -40.0);
+        let mut frame = MeasurementFrame::new(0, 48000, 2);
+        // -50 dBFS is silent at a threshold of -40
+        frame.stereo[0].peak = 0.00316;
+        metrics.update(&frame);
+
+        let text = metrics.render();
+        assert!(text.contains("# HELP jack_meter_silence_seconds The time since the channel last exceeded -40 dBFS in seconds\n"));
+        assert!(text.contains("jack_meter_silence_seconds{channel=\"1\"} 1.00\n"));
+    }
+
+    #[test]
+    fn mid_side_mode_exports_the_inputs() {
+        let mut metrics = Metrics::new(48000.0, -60.0);
+        let mut frame = MeasurementFrame::new(0, 48000, 2);
+        // A mono programme has all of its level in the mid and none in the side
+        frame.channels[0].short_term = -20.0;
+        frame.channels[1].short_term = f32::NEG_INFINITY;
+        frame.stereo[0].short_term = -23.0;
+        frame.stereo[1].short_term = -23.0;
+        metrics.update(&frame);
+
+        let text = metrics.render();
+        assert!(text.contains("jack_meter_short_term_lufs{channel=\"1\"} -23.00\n"));
+        assert!(text.contains("jack_meter_short_term_lufs{channel=\"2\"} -23.00\n"));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::config::MidiConfig;
+use crate::measurement::MAX_CHANNELS;
+
+/// The status byte of a control change, without the channel
+const CONTROL_CHANGE: u8 = 0xB0;
+/// The offset of the controller carrying the least significant bits of a 14-bit value
+const LSB_OFFSET: u8 = 32;
+
+/// The resolution of the level messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CcResolution {
+    /// A single controller with values from 0 to 127
+    Coarse,
+    /// A pair of controllers, the most significant bits on the controller
+    /// and the least significant bits on the controller 32 above it, with values from 0 to 16383
+    Fine,
+}
+
+impl CcResolution {
+    /// The highest value of a message
+    fn max(&self) -> u16 {
+        match self {
+            CcResolution::Coarse => 127,
+            CcResolution::Fine => 16383,
+        }
+    }
+}
+
+/// Turns the levels of the channels into MIDI control changes in the process callback.
+///
+/// The peak of every channel is collected sample by sample and sent at a fixed rate,
+/// at the exact sample the period ends. The level is mapped through the configured
+/// [`crate::meter_new::MeterScale`], so a controller shows the same position as a meter.
+/// Values are only sent when they changed since the last message of the channel.
+/// The controllers of all channels have to be valid, which the configuration checks.
+/// Everything is kept in fixed arrays, so this is real-time safe.
+pub struct MidiLevels {
+    config: MidiConfig,
+    /// The amount of samples between two updates
+    period: u64,
+    /// The samples left until the next update
+    countdown: u64,
+    /// The peak of every channel since the last update
+    peaks: [f32; MAX_CHANNELS],
+    /// The value that was last sent for every channel
+    sent: [Option<u16>; MAX_CHANNELS],
+}
+
+impl MidiLevels {
+    pub fn new(config: MidiConfig, sample_rate: f32) -> Self {
+        let period = ((sample_rate / config.rate) as u64).max(1);
+
+        Self {
+            config,
+            period,
+            countdown: period,
+            peaks: [0.0; MAX_CHANNELS],
+            sent: [None; MAX_CHANNELS],
+        }
+    }
+
+    /// Collect a process cycle of the channels and write the updates that fall into it
+    pub fn process(&mut self, inputs: &[&[f32]], writer: &mut jack::MidiWriter) {
+        self.process_with(inputs, |time, bytes| {
+            writer.write(&jack::RawMidi { time, bytes }).is_ok()
+        });
+    }
+
+    /// Collect a process cycle of the channels and pass the messages of the updates that fall
+    /// into it to `write`, with their time in the cycle. `write` returns whether it succeeded.
+    fn process_with(&mut self, inputs: &[&[f32]], mut write: impl FnMut(u32, &[u8]) -> bool) {
+        let length = inputs.first().map_or(0, |input| input.len());
+        let channels = inputs.len().min(MAX_CHANNELS);
+
+        for time in 0..length {
+            for (peak, input) in self.peaks.iter_mut().zip(&inputs[..channels]) {
+                *peak = peak.max(input[time].abs());
+            }
+
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.countdown = self.period;
+                self.send(channels, time as u32, &mut write);
+            }
+        }
+    }
+
+    /// Write the changed levels at the time in the cycle and start a new period
+    fn send(&mut self, channels: usize, time: u32, write: &mut impl FnMut(u32, &[u8]) -> bool) {
+        let status = CONTROL_CHANGE | (self.config.channel & 0x0F);
+        let max = self.config.resolution.max();
+
+        for channel in 0..channels {
+            let pos = self.config.scale.map(self.peaks[channel].min(1.0));
+            let value = (pos * max as f32).round() as u16;
+            self.peaks[channel] = 0.0;
+
+            if self.sent[channel] == Some(value) {
+                continue;
+            }
+
+            let controller = self.config.controller + channel as u8;
+            // If the buffer of the port is full the update is dropped and sent with the next change
+            let written = match self.config.resolution {
+                CcResolution::Coarse => write(time, &[status, controller, value as u8]),
+                CcResolution::Fine => {
+                    write(time, &[status, controller, (value >> 7) as u8])
+                        && write(
+                            time,
+                            &[status, controller + LSB_OFFSET, (value & 0x7F) as u8],
+                        )
+                }
+            };
+
+            if written {
+                self.sent[channel] = Some(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_new::MeterScale;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn levels(resolution: CcResolution) -> MidiLevels {
+        let config = MidiConfig {
+            channel: 2,
+            controller: 20,
+            resolution,
+            rate: 10.0,
+            scale: MeterScale::Linear,
+        };
+        MidiLevels::new(config, SAMPLE_RATE)
+    }
+
+    /// Process the inputs and return the messages that were written
+    fn process(levels: &mut MidiLevels, inputs: &[&[f32]]) -> Vec<(u32, Vec<u8>)> {
+        let mut messages = Vec::new();
+        levels.process_with(inputs, |time, bytes| {
+            messages.push((time, bytes.to_vec()));
+            true
+        });
+        messages
+    }
+
+    #[test]
+    fn sends_at_the_end_of_every_period() {
+        let mut levels = levels(CcResolution::Coarse);
+        let mut input = vec![0.0; 250];
+        input[50] = 0.5;
+        input[150] = 1.0;
+
+        let messages = process(&mut levels, &[&input]);
+        assert_eq!(
+            messages,
+            vec![(99, vec![0xB2, 20, 64]), (199, vec![0xB2, 20, 127])]
+        );
+
+        // The rest of the last period carries over into the next cycle
+        let messages = process(&mut levels, &[&[0.0; 50]]);
+        assert_eq!(messages, vec![(49, vec![0xB2, 20, 0])]);
+    }
+
+    #[test]
+    fn only_sends_changes() {
+        let mut levels = levels(CcResolution::Coarse);
+        let input = [0.5; 300];
+
+        let messages = process(&mut levels, &[&input, &input]);
+        assert_eq!(
+            messages,
+            vec![(99, vec![0xB2, 20, 64]), (99, vec![0xB2, 21, 64])]
+        );
+    }
+
+    #[test]
+    fn failed_writes_are_sent_again() {
+        let mut levels = levels(CcResolution::Coarse);
+        levels.process_with(&[&[0.5; 100]], |_, _| false);
+
+        let messages = process(&mut levels, &[&[0.5; 100]]);
+        assert_eq!(messages, vec![(99, vec![0xB2, 20, 64])]);
+    }
+
+    #[test]
+    fn fine_values_are_split_into_msb_and_lsb() {
+        let mut levels = levels(CcResolution::Fine);
+        // 0.1 of 16383 is 1638, which is 12 * 128 + 102
+        let messages = process(&mut levels, &[&[0.1; 100]]);
+        assert_eq!(
+            messages,
+            vec![(99, vec![0xB2, 20, 12]), (99, vec![0xB2, 52, 102])]
+        );
+
+        let messages = process(&mut levels, &[&[1.0; 100]]);
+        assert_eq!(
+            messages,
+            vec![(99, vec![0xB2, 20, 0x7F]), (99, vec![0xB2, 52, 0x7F])]
+        );
+    }
+}
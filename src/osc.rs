@@ -0,0 +1,453 @@
+use crate::compliance::LoudnessProfile;
+use crate::config::OscConfig;
+use crate::lin2db;
+use crate::measurement::MeasurementFrame;
+use crate::meter_new::{MeterEvents, MeterScale};
+use crate::silence::SilenceChange;
+use std::io;
+use std::net::UdpSocket;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The prefix of all addresses
+const ADDRESS_PREFIX: &str = "/jack_meter";
+/// The largest packet the listener accepts
+const MAX_PACKET_SIZE: usize = 1536;
+
+/// A single argument of an OSC message
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl OscArg {
+    /// The value of a numeric argument
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            OscArg::Int(value) => Some(*value as f32),
+            OscArg::Float(value) => Some(*value),
+            OscArg::String(_) => None,
+        }
+    }
+}
+
+/// An OSC 1.0 message
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(address: impl Into<String>, args: Vec<OscArg>) -> Self {
+        Self {
+            address: address.into(),
+            args,
+        }
+    }
+
+    /// Encode the message into the bytes of a UDP packet
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, &self.address);
+
+        let mut type_tags = String::from(",");
+        for arg in &self.args {
+            type_tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+            });
+        }
+        write_string(&mut bytes, &type_tags);
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::Float(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::String(value) => write_string(&mut bytes, value),
+            }
+        }
+
+        bytes
+    }
+
+    /// Decode a message from the bytes of a UDP packet.
+    /// Bundles and unsupported argument types are rejected.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut position = 0;
+        let address = read_string(bytes, &mut position)?;
+        if !address.starts_with('/') {
+            return None;
+        }
+
+        // Messages without a type tag string are allowed by OSC 1.0 and have no arguments
+        if position == bytes.len() {
+            return Some(Self::new(address, Vec::new()));
+        }
+
+        let type_tags = read_string(bytes, &mut position)?;
+        let mut args = Vec::new();
+        for tag in type_tags.strip_prefix(',')?.chars() {
+            args.push(match tag {
+                'i' => OscArg::Int(i32::from_be_bytes(read_word(bytes, &mut position)?)),
+                'f' => OscArg::Float(f32::from_be_bytes(read_word(bytes, &mut position)?)),
+                's' => OscArg::String(read_string(bytes, &mut position)?),
+                _ => return None,
+            });
+        }
+
+        Some(Self::new(address, args))
+    }
+}
+
+/// Read a padded string and move the position past its padding
+fn read_string(bytes: &[u8], position: &mut usize) -> Option<String> {
+    let rest = bytes.get(*position..)?;
+    let length = rest.iter().position(|byte| *byte == 0)?;
+    let string = std::str::from_utf8(&rest[..length]).ok()?.to_string();
+    *position += length + 4 - length % 4;
+    Some(string)
+}
+
+/// Read the four bytes of a number and move the position past them
+fn read_word(bytes: &[u8], position: &mut usize) -> Option<[u8; 4]> {
+    let word = bytes.get(*position..*position + 4)?.try_into().ok()?;
+    *position += 4;
+    Some(word)
+}
+
+/// Write a string terminated by at least one null byte and padded to a multiple of four bytes
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend_from_slice(string.as_bytes());
+    let padding = 4 - string.len() % 4;
+    bytes.extend(std::iter::repeat_n(0, padding));
+}
+
+/// The messages for all channels of a frame.
+///
+/// Every channel sends its values in dBFS or LUFS to `/jack_meter/<channel>/<value>`,
+/// with the channels numbered from 1 and the values `peak`, `true_peak`, `rms`,
+/// `momentary`, `short_term` and `integrated`.
+pub fn frame_messages(frame: &MeasurementFrame) -> Vec<OscMessage> {
+    let mut messages = Vec::new();
+
+    for (index, channel) in frame.channels().iter().enumerate() {
+        let values = [
+            ("peak", lin2db(channel.peak)),
+            ("true_peak", lin2db(channel.true_peak)),
+            ("rms", lin2db(channel.rms)),
+            ("momentary", channel.momentary),
+            ("short_term", channel.short_term),
+            ("integrated", channel.integrated),
+        ];
+
+        for (name, value) in values {
+            messages.push(OscMessage::new(
+                format!("{}/{}/{}", ADDRESS_PREFIX, index + 1, name),
+                vec![OscArg::Float(value)],
+            ));
+        }
+    }
+
+    messages
+}
+
+/// Start sending the levels as OSC messages over UDP until the receiver hangs up.
+///
+/// The frames are collected on a thread of its own and combined into one update per period,
+/// so the amount of packets only depends on the configured rate.
+/// The output can be checked with any UDP listener, e.g. `nc -ul 9000`
+/// after starting with `--osc-target 127.0.0.1:9000`.
+pub fn spawn_sender(
+    config: &OscConfig,
+    frames: Receiver<MeasurementFrame>,
+) -> io::Result<JoinHandle<()>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(&config.target)?;
+    let period = Duration::from_secs_f32(1.0 / config.rate);
+
+    Ok(thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut next_send = Instant::now() + period;
+
+        loop {
+            match frames.recv_timeout(next_send.saturating_duration_since(Instant::now())) {
+                Ok(frame) => pending.push(frame),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if now < next_send {
+                continue;
+            }
+            // Skip the updates that were missed instead of sending them in a burst
+            next_send = (next_send + period).max(now);
+
+            if let Some(frame) = MeasurementFrame::combine(&pending) {
+                for message in frame_messages(&frame) {
+                    // A missing listener isn't an error worth stopping for
+                    let _ = socket.send(&message.encode());
+                }
+            }
+            pending.clear();
+        }
+    }))
+}
+
+/// The message announcing that a channel went silent (1) or recovered (0),
+/// as `/jack_meter/<channel>/silence i`
+pub fn silence_message(change: &SilenceChange) -> OscMessage {
+    OscMessage::new(
+        format!("{}/{}/silence", ADDRESS_PREFIX, change.channel + 1),
+        vec![OscArg::Int(change.silent as i32)],
+    )
+}
+
+/// A remote control command received over OSC
+#[derive(Debug, Clone)]
+pub enum OscCommand {
+    /// A setting or reset applied to all meters, as if it was changed in the GUI
+    Meter(MeterEvents),
+    /// Clear the integrated loudness
+    ResetIntegrated,
+    /// Start or stop the integration of the loudness
+    SetIntegrating(bool),
+    /// Change the loudness standard the programme is checked against
+    SetStandard(LoudnessProfile),
+}
+
+impl OscCommand {
+    /// Map a message onto the command it stands for.
+    ///
+    /// The supported addresses are:
+    /// - `/jack_meter/reset`: reset the peak holds, maximums and clip indicators
+    /// - `/jack_meter/reset/integrated`: reset the integrated loudness
+    /// - `/jack_meter/integration/start` and `/jack_meter/integration/stop`
+    /// - `/jack_meter/scale <s>`: `linear` or `logarithmic`
+    /// - `/jack_meter/standard <s>`: the key of a loudness standard, like `ebu` or `atsc`
+    /// - `/jack_meter/peak_drop_speed <f>`
+    /// - `/jack_meter/smoothing <f>`
+    /// - `/jack_meter/hold_time <i>`
+    /// - `/jack_meter/infinite_hold <i>`: 1 to hold the peaks until they are reset, 0 to let them drop
+    pub fn from_message(message: &OscMessage) -> Option<Self> {
+        let path = message.address.strip_prefix(ADDRESS_PREFIX)?;
+        let number = || message.args.first().and_then(OscArg::as_f32);
+
+        let command = match path {
+            "/reset" => OscCommand::Meter(MeterEvents::ResetHold),
+            "/reset/integrated" => OscCommand::ResetIntegrated,
+            "/integration/start" => OscCommand::SetIntegrating(true),
+            "/integration/stop" => OscCommand::SetIntegrating(false),
+            "/scale" => {
+                let scale = match message.args.first()? {
+                    OscArg::String(scale) if scale == "linear" => MeterScale::Linear,
+                    OscArg::String(scale) if scale == "logarithmic" => MeterScale::Logarithmic,
+                    _ => return None,
+                };
+                OscCommand::Meter(MeterEvents::ChangeMeterScale(scale))
+            }
+            "/standard" => match message.args.first()? {
+                OscArg::String(key) => OscCommand::SetStandard(LoudnessProfile::from_key(key)?),
+                _ => return None,
+            },
+            "/peak_drop_speed" => OscCommand::Meter(MeterEvents::ChangePeakDropSpeed(number()?)),
+            // The smoothing factor has to stay in (0,1]
+            "/smoothing" => OscCommand::Meter(MeterEvents::ChangeSmoothingFactor(
+                number()?.clamp(0.001, 1.0),
+            )),
+            // A negative hold time would never run out
+            "/hold_time" => {
+                OscCommand::Meter(MeterEvents::ChangeMaxHoldTime(number()?.max(0.0) as i32))
+            }
+            "/infinite_hold" => {
+                OscCommand::Meter(MeterEvents::ChangeInfiniteHold(number()? != 0.0))
+            }
+            _ => return None,
+        };
+
+        Some(command)
+    }
+}
+
+/// Start listening for OSC commands on the address and pass them on to the sender
+/// until it hangs up. Packets that aren't a known command are ignored.
+pub fn spawn_listener(address: &str, commands: Sender<OscCommand>) -> io::Result<JoinHandle<()>> {
+    let socket = UdpSocket::bind(address)?;
+
+    Ok(thread::spawn(move || {
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+
+        while let Ok(length) = socket.recv(&mut buffer) {
+            let command = OscMessage::decode(&buffer[..length])
+                .as_ref()
+                .and_then(OscCommand::from_message);
+
+            if let Some(command) = command {
+                if commands.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_padded_strings_and_big_endian_arguments() {
+        let message = OscMessage::new("/a", vec![OscArg::Int(1), OscArg::Float(0.5)]);
+        assert_eq!(
+            message.encode(),
+            b"/a\0\0,if\0\0\0\0\x01\x3f\0\0\0".to_vec()
+        );
+    }
+
+    fn command(address: &str, args: Vec<OscArg>) -> Option<OscCommand> {
+        let bytes = OscMessage::new(address, args).encode();
+        OscCommand::from_message(&OscMessage::decode(&bytes)?)
+    }
+
+    #[test]
+    fn decodes_commands() {
+        assert!(matches!(
+            command("/jack_meter/reset", vec![]),
+            Some(OscCommand::Meter(MeterEvents::ResetHold))
+        ));
+        assert!(matches!(
+            command("/jack_meter/reset/integrated", vec![]),
+            Some(OscCommand::ResetIntegrated)
+        ));
+        assert!(matches!(
+            command("/jack_meter/integration/stop", vec![]),
+            Some(OscCommand::SetIntegrating(false))
+        ));
+        assert!(matches!(
+            command("/jack_meter/scale", vec![OscArg::String("linear".into())]),
+            Some(OscCommand::Meter(MeterEvents::ChangeMeterScale(
+                MeterScale::Linear
+            )))
+        ));
+        assert!(matches!(
+            command("/jack_meter/standard", vec![OscArg::String("atsc".into())]),
+            Some(OscCommand::SetStandard(LoudnessProfile { key: "atsc", .. }))
+        ));
+        assert!(matches!(
+            command("/jack_meter/hold_time", vec![OscArg::Int(40)]),
+            Some(OscCommand::Meter(MeterEvents::ChangeMaxHoldTime(40)))
+        ));
+        assert!(matches!(
+            command("/jack_meter/infinite_hold", vec![OscArg::Float(1.0)]),
+            Some(OscCommand::Meter(MeterEvents::ChangeInfiniteHold(true)))
+        ));
+    }
+
+    #[test]
+    fn clamps_command_values() {
+        assert!(matches!(
+            command("/jack_meter/hold_time", vec![OscArg::Int(-5)]),
+            Some(OscCommand::Meter(MeterEvents::ChangeMaxHoldTime(0)))
+        ));
+        match command("/jack_meter/smoothing", vec![OscArg::Float(0.0)]) {
+            Some(OscCommand::Meter(MeterEvents::ChangeSmoothingFactor(factor))) => {
+                assert!(factor > 0.0)
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        assert!(command("/jack_meter/unknown", vec![]).is_none());
+        assert!(command("/other/reset", vec![]).is_none());
+        assert!(command("/jack_meter/scale", vec![OscArg::Int(1)]).is_none());
+        assert!(command("/jack_meter/standard", vec![OscArg::String("r128".into())]).is_none());
+        assert!(command("/jack_meter/hold_time", vec![]).is_none());
+        assert!(OscMessage::decode(b"no_slash\0\0\0\0").is_none());
+    }
+
+    #[test]
+    fn frame_messages_name_every_value() {
+        let mut frame = MeasurementFrame::new(0, 512, 2);
+        frame.channels[0].peak = 0.5;
+        frame.channels[1].short_term = -23.0;
+        let messages = frame_messages(&frame);
+
+        assert_eq!(messages.len(), 12);
+        assert_eq!(messages[0].address, "/jack_meter/1/peak");
+        assert_eq!(messages[0].args, vec![OscArg::Float(lin2db(0.5))]);
+        assert_eq!(messages[10].address, "/jack_meter/2/short_term");
+        assert_eq!(messages[10].args, vec![OscArg::Float(-23.0)]);
+    }
+
+    #[test]
+    fn sender_combines_the_frames_of_a_period() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let config = OscConfig {
+            target: listener.local_addr().unwrap().to_string(),
+            rate: 20.0,
+        };
+        let (frames, receiver) = std::sync::mpsc::channel();
+        let sender = spawn_sender(&config, receiver).unwrap();
+
+        // Both frames arrive within the first period, so they are sent as a single update
+        let mut quiet = MeasurementFrame::new(0, 512, 2);
+        quiet.channels[0].peak = 0.25;
+        quiet.channels[1].short_term = -30.0;
+        let mut loud = MeasurementFrame::new(512, 512, 2);
+        loud.channels[0].peak = 0.5;
+        loud.channels[1].short_term = -23.0;
+        frames.send(quiet).unwrap();
+        frames.send(loud).unwrap();
+
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        let mut messages = Vec::new();
+        for _ in 0..12 {
+            let length = listener.recv(&mut buffer).unwrap();
+            messages.push(OscMessage::decode(&buffer[..length]).unwrap());
+        }
+        assert_eq!(
+            messages,
+            frame_messages(&MeasurementFrame::combine(&[quiet, loud]).unwrap())
+        );
+        assert_eq!(messages[0].args, vec![OscArg::Float(lin2db(0.5))]);
+        assert_eq!(messages[10].args, vec![OscArg::Float(-23.0)]);
+
+        // A steady stream of frames is sent at the configured rate, not once per frame
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            frames.send(loud).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let elapsed = start.elapsed();
+        drop(frames);
+        sender.join().unwrap();
+
+        listener
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut packets = 0;
+        while listener.recv(&mut buffer).is_ok() {
+            packets += 1;
+        }
+        assert_eq!(packets % 12, 0);
+
+        let periods = elapsed.as_secs_f32() * config.rate;
+        let updates = (packets / 12) as f32;
+        assert!(
+            updates <= periods + 1.0 && updates >= periods / 2.0,
+            "{} updates in {} periods",
+            updates,
+            periods
+        );
+    }
+}
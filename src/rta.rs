@@ -0,0 +1,389 @@
+use crate::biquad::{Biquad, Cascade};
+use crate::meter_new::{Direction, Meter, MeterHandle};
+use crate::weighting::Weighting;
+use atomic_float::AtomicF32;
+use std::f64::consts::PI;
+use std::sync::atomic::Ordering;
+use vizia::*;
+
+/// The amount of third-octave bands between 20 Hz and 20 kHz
+pub const THIRD_OCTAVE_BANDS: usize = 31;
+
+/// The nominal centre frequencies as printed on the band labels (IEC 61260-1 Annex E)
+const NOMINAL_THIRD_OCTAVE: [&str; THIRD_OCTAVE_BANDS] = [
+    "20", "25", "31.5", "40", "50", "63", "80", "100", "125", "160", "200", "250", "315", "400",
+    "500", "630", "800", "1k", "1.25k", "1.6k", "2k", "2.5k", "3.15k", "4k", "5k", "6.3k", "8k",
+    "10k", "12.5k", "16k", "20k",
+];
+
+/// The time constant of the band level averaging in seconds.
+/// This is the "Fast" time weighting of IEC 61672-1.
+const TIME_CONSTANT: f64 = 0.125;
+
+/// The width of the bands of the analyser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum Bandwidth {
+    /// 10 bands from 31.5 Hz to 16 kHz
+    Octave,
+    /// 31 bands from 20 Hz to 20 kHz
+    ThirdOctave,
+}
+
+impl Bandwidth {
+    /// The bandwidth designator `b` of IEC 61260-1, i.e. the fraction of an octave per band
+    fn designator(&self) -> f64 {
+        match self {
+            Bandwidth::Octave => 1.0,
+            Bandwidth::ThirdOctave => 3.0,
+        }
+    }
+
+    /// The band indices `x` relative to 1 kHz that are shown by the analyser
+    fn indices(&self) -> impl Iterator<Item = i32> {
+        match self {
+            Bandwidth::Octave => (-15..=12).step_by(3),
+            Bandwidth::ThirdOctave => (-17..=13).step_by(1),
+        }
+    }
+
+    /// The exact mid-band frequencies using the base-ten octave ratio G = 10^(3/10)
+    pub fn centre_frequencies(&self) -> Vec<f64> {
+        self.indices()
+            .map(|x| 1000.0 * 10f64.powf(x as f64 / 10.0))
+            .collect()
+    }
+
+    /// The lower and upper band edge frequencies of a band with the mid-band frequency `fm`
+    pub fn band_edges(&self, fm: f64) -> (f64, f64) {
+        let ratio = 10f64.powf(3.0 / 10.0).powf(1.0 / (2.0 * self.designator()));
+        (fm / ratio, fm * ratio)
+    }
+
+    /// The labels of the bands using the nominal frequencies
+    pub fn labels(&self) -> Vec<&'static str> {
+        self.indices()
+            .map(|x| NOMINAL_THIRD_OCTAVE[(x + 17) as usize])
+            .collect()
+    }
+}
+
+/// A minimal complex number, only used to design the band filters
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+
+    fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+
+    fn div(self, o: Self) -> Self {
+        let d = o.re * o.re + o.im * o.im;
+        Self::new(
+            (self.re * o.re + self.im * o.im) / d,
+            (self.im * o.re - self.re * o.im) / d,
+        )
+    }
+
+    fn sqrt(self) -> Self {
+        let r = (self.re * self.re + self.im * self.im).sqrt();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt().copysign(self.im);
+        Self::new(re, im)
+    }
+}
+
+/// Design a 6th order Butterworth band-pass between `f1` and `f2`.
+///
+/// This is the filter order usually used to satisfy the class 1 limits of IEC 61260-1.
+/// The analogue prototype is transformed to a band-pass and mapped using the
+/// bilinear transform with both edges prewarped, so the edges land exactly on `f1` and `f2`.
+pub fn band_pass(f1: f64, f2: f64, sample_rate: f64) -> Cascade {
+    let k = 2.0 * sample_rate;
+    let w1 = k * (PI * f1 / sample_rate).tan();
+    let w2 = k * (PI * f2 / sample_rate).tan();
+    let w0_sq = Complex::new(w1 * w2, 0.0);
+    let half_bw = Complex::new((w2 - w1) / 2.0, 0.0);
+
+    // The third order prototype has one conjugate pole pair at 120°/240° and one real pole.
+    // Each prototype pole turns into two band-pass poles, of which we only need
+    // one of each conjugate pair to build the second order sections.
+    let pair = Complex::new((2.0 * PI / 3.0).cos(), (2.0 * PI / 3.0).sin());
+    let real = Complex::new(-1.0, 0.0);
+
+    let mut poles = Vec::with_capacity(3);
+    for p in [pair, real] {
+        let h = p.mul(half_bw);
+        let d = h.mul(h).sub(w0_sq).sqrt();
+        poles.push(h.add(d));
+        if p.im != 0.0 {
+            poles.push(h.sub(d));
+        }
+    }
+
+    // The geometric centre of the prewarped edges, mapped back to the digital domain
+    let centre = 2.0 * ((w1 * w2).sqrt() / k).atan();
+
+    let sections = poles
+        .into_iter()
+        .map(|s| {
+            let k = Complex::new(k, 0.0);
+            let z = k.add(s).div(k.sub(s));
+
+            // One zero at DC and one at Nyquist per section
+            let mut section = Biquad::new(1.0, 0.0, -1.0, -2.0 * z.re, z.re * z.re + z.im * z.im);
+            section.scale(1.0 / section.magnitude(centre));
+            section
+        })
+        .collect();
+
+    Cascade::new(sections)
+}
+
+struct Band {
+    /// The band filter, or `None` if the band reaches above the nyquist frequency
+    filter: Option<Cascade>,
+    /// The time weighted mean square of the filter output
+    mean_square: f64,
+}
+
+/// The filter bank that runs in the audio thread and measures the level of every band
+pub struct FilterBank {
+    bands: Vec<Band>,
+    /// The coefficient of the exponential averaging
+    alpha: f64,
+}
+
+impl FilterBank {
+    pub fn new(bandwidth: Bandwidth, sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+
+        let bands = bandwidth
+            .centre_frequencies()
+            .into_iter()
+            .map(|fm| {
+                let (f1, f2) = bandwidth.band_edges(fm);
+                Band {
+                    filter: (f2 < sample_rate / 2.0).then(|| band_pass(f1, f2, sample_rate)),
+                    mean_square: 0.0,
+                }
+            })
+            .collect();
+
+        Self {
+            bands,
+            alpha: 1.0 - (-1.0 / (TIME_CONSTANT * sample_rate)).exp(),
+        }
+    }
+
+    /// Run a block of samples through every band
+    pub fn process(&mut self, input: &[f32]) {
+        let alpha = self.alpha;
+
+        for band in self.bands.iter_mut() {
+            if let Some(filter) = band.filter.as_mut() {
+                for sample in input {
+                    let y = filter.process(*sample as f64);
+                    band.mean_square += alpha * (y * y - band.mean_square);
+                }
+            }
+        }
+    }
+
+    /// Store the current RMS level of every band
+    pub fn store(&self, levels: &[AtomicF32]) {
+        for (band, level) in self.bands.iter().zip(levels) {
+            level.store(band.mean_square.sqrt() as f32, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The different events that can be called to update states in the analyser
+#[derive(Debug, Clone)]
+pub enum RtaEvents {
+    /// Update the unweighted band levels
+    UpdateLevels(Vec<f32>),
+    /// Change the frequency weighting applied to the band levels
+    ChangeWeighting(Weighting),
+}
+
+/// A real time analyser showing the level of each octave or third-octave band as a vertical meter.
+///
+/// As an input it requires a lens to the unweighted, linear RMS level of every band,
+/// as measured by a [`FilterBank`]. The weighting is applied to the levels on display
+/// and can be changed at any time using the `weighting` handle.
+///
+/// Example:
+/// ```rust
+/// Data{bands: vec![0.0; THIRD_OCTAVE_BANDS]}.build(cx);
+///
+/// Rta::new(cx, Data::bands, Bandwidth::ThirdOctave)
+/// .weighting(Weighting::A);
+/// ```
+#[derive(Lens)]
+pub struct Rta {
+    /// The weighted level of every band in [0,1]
+    levels: Vec<f32>,
+    /// The weighting gain of every band
+    gains: Vec<f32>,
+    /// The mid-band frequencies
+    centres: Vec<f32>,
+}
+
+impl Rta {
+    pub fn new<L: Lens<Target = Vec<f32>>>(
+        cx: &mut Context,
+        lens: L,
+        bandwidth: Bandwidth,
+    ) -> Handle<Self> {
+        let centres: Vec<f32> = bandwidth
+            .centre_frequencies()
+            .into_iter()
+            .map(|f| f as f32)
+            .collect();
+        let labels = bandwidth.labels();
+
+        Self {
+            levels: vec![0.0; centres.len()],
+            gains: vec![1.0; centres.len()],
+            centres,
+        }
+        .build(cx, move |cx| {
+            Binding::new(cx, lens, |cx, value| {
+                cx.emit(RtaEvents::UpdateLevels(value.get(cx).clone()));
+            });
+
+            HStack::new(cx, move |cx| {
+                for (i, label) in labels.into_iter().enumerate() {
+                    VStack::new(cx, move |cx| {
+                        Meter::new(
+                            cx,
+                            Rta::levels.map(move |levels| levels.get(i).copied().unwrap_or(0.0)),
+                            Direction::Up,
+                        )
+                        .smoothing_factor(1.0)
+                        .max_hold_time(40);
+
+                        Label::new(cx, label)
+                            .height(Pixels(20.0))
+                            .class("rta_label");
+                    })
+                    .class("rta_band");
+                }
+            });
+        })
+    }
+}
+
+impl View for Rta {
+    fn element(&self) -> Option<String> {
+        Some("rta".to_string())
+    }
+
+    fn event(&mut self, _cx: &mut Context, event: &mut Event) {
+        event.map(|rta_event, _| match rta_event {
+            RtaEvents::UpdateLevels(levels) => {
+                self.levels = levels
+                    .iter()
+                    .zip(&self.gains)
+                    .map(|(level, gain)| level * gain)
+                    .collect();
+            }
+            RtaEvents::ChangeWeighting(weighting) => {
+                self.gains = self.centres.iter().map(|f| weighting.gain(*f)).collect();
+            }
+        });
+    }
+}
+
+pub trait RtaHandle {
+    fn weighting(self, val: impl Res<Weighting>) -> Self;
+}
+
+impl RtaHandle for Handle<'_, Rta> {
+    fn weighting(self, val: impl Res<Weighting>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, RtaEvents::ChangeWeighting(value));
+        });
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db(gain: f64) -> f64 {
+        20.0 * gain.log10()
+    }
+
+    #[test]
+    fn centre_frequencies_are_base_ten_around_1k() {
+        let third = Bandwidth::ThirdOctave.centre_frequencies();
+        assert_eq!(third.len(), THIRD_OCTAVE_BANDS);
+        assert!((third[17] - 1000.0).abs() < 1e-9);
+        for pair in third.windows(2) {
+            assert!((pair[1] / pair[0] - 10f64.powf(0.1)).abs() < 1e-9);
+        }
+
+        let octave = Bandwidth::Octave.centre_frequencies();
+        assert_eq!(octave.len(), 10);
+        assert!((octave[5] - 1000.0).abs() < 1e-9);
+        for pair in octave.windows(2) {
+            assert!((pair[1] / pair[0] - 10f64.powf(0.3)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn band_edges_are_centred_geometrically() {
+        let (f1, f2) = Bandwidth::ThirdOctave.band_edges(1000.0);
+        assert!((f1 - 891.2509).abs() < 1e-3);
+        assert!((f2 - 1122.0185).abs() < 1e-3);
+        assert!((f1 * f2 - 1e6).abs() < 1e-6);
+
+        let (f1, f2) = Bandwidth::Octave.band_edges(1000.0);
+        assert!((f1 - 707.9458).abs() < 1e-3);
+        assert!((f2 - 1412.5375).abs() < 1e-3);
+    }
+
+    #[test]
+    fn band_pass_is_3db_down_at_the_edges() {
+        let sample_rate = 48000.0;
+        let angle = |f: f64| 2.0 * PI * f / sample_rate;
+
+        for fm in Bandwidth::ThirdOctave.centre_frequencies() {
+            let (f1, f2) = Bandwidth::ThirdOctave.band_edges(fm);
+            if f2 >= sample_rate / 2.0 {
+                continue;
+            }
+            let filter = band_pass(f1, f2, sample_rate);
+
+            // The centre of the digital filter is the geometric centre of the prewarped edges,
+            // which is slightly off from `fm` close to the nyquist frequency
+            let centre = filter.magnitude(angle(fm));
+            assert!(db(centre).abs() < 0.1, "{} Hz: {} dB", fm, db(centre));
+            for edge in [f1, f2] {
+                let gain = db(filter.magnitude(angle(edge)));
+                assert!((gain + 3.01).abs() < 0.01, "{} Hz: {} dB", edge, gain);
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::config::SilenceConfig;
+use crate::event_log::LogEntry;
+use crate::measurement::{MeasurementFrame, MAX_CHANNELS};
+use crate::osc::silence_message;
+use crate::{db2lin, format_timestamp};
+use std::net::UdpSocket;
+use std::process::Command;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+/// A channel that went silent or recovered
+#[derive(Debug, Clone, Copy)]
+pub struct SilenceChange {
+    /// The index of the channel
+    pub channel: usize,
+    /// Whether the channel is silent now, or recovered
+    pub silent: bool,
+    /// The position in samples at which the change was detected
+    pub position: u64,
+    /// The JACK frame time of the cycle in which the change was detected
+    pub frame_time: u32,
+    /// The wall-clock time at which the change was detected
+    pub time: SystemTime,
+}
+
+/// The state of a single channel
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelSilence {
+    /// The position of the first sample of the current quiet stretch
+    quiet_since: Option<u64>,
+    silent: bool,
+}
+
+/// Detects channels that stay below a threshold for a minimum duration.
+///
+/// A channel is silent once the peak of every frame stayed at or below the threshold
+/// for the whole duration, and recovers with the first frame that exceeds it.
+/// Both changes are reported once.
+pub struct SilenceDetector {
+    /// The linear threshold
+    threshold: f32,
+    /// The duration in samples
+    duration: u64,
+    channels: [ChannelSilence; MAX_CHANNELS],
+}
+
+impl SilenceDetector {
+    pub fn new(config: &SilenceConfig, sample_rate: f32) -> Self {
+        Self {
+            threshold: db2lin(config.threshold),
+            duration: (config.duration * sample_rate) as u64,
+            channels: [ChannelSilence::default(); MAX_CHANNELS],
+        }
+    }
+
+    /// Add a frame and collect the changes it caused.
+    /// The inputs are checked in the Mid/Side mode too, since a mono programme has a silent side.
+    pub fn update(&mut self, frame: &MeasurementFrame, changes: &mut Vec<SilenceChange>) {
+        let frame = frame.stereo_frame();
+        let end = frame.position + frame.length as u64;
+
+        for (channel, (state, measurement)) in
+            self.channels.iter_mut().zip(frame.channels()).enumerate()
+        {
+            let quiet = measurement.peak <= self.threshold;
+
+            let silent = if quiet {
+                let since = *state.quiet_since.get_or_insert(frame.position);
+                end - since >= self.duration
+            } else {
+                state.quiet_since = None;
+                false
+            };
+
+            if silent != state.silent {
+                state.silent = silent;
+                changes.push(SilenceChange {
+                    channel,
+                    silent,
+                    position: end,
+                    frame_time: frame.frame_time,
+                    time: SystemTime::now(),
+                });
+            }
+        }
+    }
+}
+
+/// Run the silence detection on the frames from the receiver and pass every change on.
+///
+/// Every change is sent to `ui`, written to the event log, sent as
+/// `/jack_meter/<channel>/silence i` (1 or 0) to the OSC target if there is one,
+/// and runs the configured command. The command is run by `sh -c` with the variables
+/// `JACK_METER_CHANNEL`, `JACK_METER_STATE` (`silent` or `recovered`), `JACK_METER_TIME`
+/// and `JACK_METER_POSITION` set.
+pub fn spawn_detector(
+    config: &SilenceConfig,
+    sample_rate: f32,
+    osc_target: Option<String>,
+    frames: Receiver<MeasurementFrame>,
+    ui: Sender<SilenceChange>,
+    log: Sender<LogEntry>,
+) -> JoinHandle<()> {
+    let mut detector = SilenceDetector::new(config, sample_rate);
+    let command = config.command.clone();
+
+    thread::spawn(move || {
+        // Without a socket the changes are still logged and passed to the other hooks
+        let socket = osc_target.and_then(|target| {
+            let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+            socket.connect(target).ok()?;
+            Some(socket)
+        });
+
+        let mut changes = Vec::new();
+        while let Ok(frame) = frames.recv() {
+            detector.update(&frame, &mut changes);
+
+            for change in changes.drain(..) {
+                let time = format_timestamp(change.time);
+                let state = if change.silent { "silent" } else { "recovered" };
+                let _ = log.send(LogEntry {
+                    time: change.time,
+                    frame_time: change.frame_time,
+                    message: format!("channel {} {}", change.channel + 1, state),
+                });
+                let _ = ui.send(change);
+
+                if let Some(socket) = &socket {
+                    let _ = socket.send(&silence_message(&change).encode());
+                }
+
+                if let Some(command) = &command {
+                    let child = Command::new("sh")
+                        .arg("-c")
+                        .arg(command)
+                        .env("JACK_METER_CHANNEL", (change.channel + 1).to_string())
+                        .env("JACK_METER_STATE", state)
+                        .env("JACK_METER_TIME", &time)
+                        .env("JACK_METER_POSITION", change.position.to_string())
+                        .spawn();
+
+                    match child {
+                        // Wait on its own thread, so a slow command doesn't delay the detection
+                        Ok(mut child) => {
+                            thread::spawn(move || child.wait());
+                        }
+                        Err(err) => eprintln!("Failed to run the silence command: {}", err),
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+    const FRAME_LENGTH: u32 = 100;
+
+    fn detector() -> SilenceDetector {
+        let config = SilenceConfig {
+            threshold: -60.0,
+            duration: 1.0,
+            command: None,
+        };
+        SilenceDetector::new(&config, SAMPLE_RATE)
+    }
+
+    /// A frame with the peaks of the left and right input
+    fn frame(index: u64, left: f32, right: f32) -> MeasurementFrame {
+        let mut frame = MeasurementFrame::new(index * FRAME_LENGTH as u64, FRAME_LENGTH, 2);
+        frame.stereo[0].peak = left;
+        frame.stereo[1].peak = right;
+        frame.channels = frame.stereo_frame().channels;
+        frame
+    }
+
+    /// Add the frames and return the changes as (channel, silent, position)
+    fn update(
+        detector: &mut SilenceDetector,
+        frames: &[MeasurementFrame],
+    ) -> Vec<(usize, bool, u64)> {
+        let mut changes = Vec::new();
+        for frame in frames {
+            detector.update(frame, &mut changes);
+        }
+        changes
+            .iter()
+            .map(|change| (change.channel, change.silent, change.position))
+            .collect()
+    }
+
+    #[test]
+    fn silent_after_the_duration() {
+        let mut detector = detector();
+        let quiet: Vec<_> = (0..9).map(|index| frame(index, 0.0, 0.5)).collect();
+        assert!(update(&mut detector, &quiet).is_empty());
+
+        // The tenth quiet frame completes the second
+        assert_eq!(
+            update(&mut detector, &[frame(9, 0.0, 0.5)]),
+            vec![(0, true, 1000)]
+        );
+        // It is only reported once
+        assert!(update(&mut detector, &[frame(10, 0.0, 0.5)]).is_empty());
+    }
+
+    #[test]
+    fn a_peak_restarts_the_duration() {
+        let mut detector = detector();
+        let mut frames: Vec<_> = (0..5).map(|index| frame(index, 0.0, 0.5)).collect();
+        frames.push(frame(5, 0.5, 0.5));
+        frames.extend((6..15).map(|index| frame(index, 0.0, 0.5)));
+
+        assert!(update(&mut detector, &frames).is_empty());
+    }
+
+    #[test]
+    fn recovers_with_the_first_loud_frame() {
+        let mut detector = detector();
+        let quiet: Vec<_> = (0..10).map(|index| frame(index, 0.0, 0.0)).collect();
+        assert_eq!(
+            update(&mut detector, &quiet),
+            vec![(0, true, 1000), (1, true, 1000)]
+        );
+
+        assert_eq!(
+            update(&mut detector, &[frame(10, 0.0, 0.5)]),
+            vec![(1, false, 1100)]
+        );
+    }
+
+    #[test]
+    fn mono_in_mid_side_mode_is_not_silent() {
+        let mut detector = detector();
+        // The meters show a loud mid and a silent side, the inputs are both loud
+        let frames: Vec<_> = (0..20)
+            .map(|index| {
+                let mut frame = frame(index, 0.5, 0.5);
+                frame.channels[0].peak = 0.5;
+                frame.channels[1].peak = 0.0;
+                frame
+            })
+            .collect();
+
+        assert!(update(&mut detector, &frames).is_empty());
+    }
+}
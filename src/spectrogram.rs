@@ -0,0 +1,330 @@
+use crate::meter_new::MeterScale;
+use crate::spectrum::SpectrumFrames;
+use std::cell::{Cell, RefCell};
+use std::sync::Mutex;
+use vizia::vg::imgref::ImgRef;
+use vizia::vg::rgb::RGBA8;
+use vizia::vg::{ImageFlags, ImageId, Paint, Path, PixelFormat};
+use vizia::*;
+
+/// The amount of spectra the image keeps
+const COLUMNS: usize = 512;
+/// The amount of frequency rows of the image
+const ROWS: usize = 256;
+/// The lowest frequency shown
+const MIN_FREQUENCY: f32 = 20.0;
+
+/// The images of spectrograms that were dropped.
+/// An image can only be deleted through the canvas, so the next spectrogram that is drawn does it.
+static RELEASED_IMAGES: Mutex<Vec<ImageId>> = Mutex::new(Vec::new());
+
+/// The colour maps a spectrogram can use to show the levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum ColorMap {
+    /// From black to white
+    Grayscale,
+    /// From black over red and yellow to white
+    Heat,
+    /// A perceptually uniform map from dark blue over green to yellow
+    Viridis,
+}
+
+impl ColorMap {
+    /// The colours along the map, evenly spaced in [0,1]
+    fn stops(&self) -> &'static [(u8, u8, u8)] {
+        match self {
+            ColorMap::Grayscale => &[(0, 0, 0), (255, 255, 255)],
+            ColorMap::Heat => &[(0, 0, 0), (180, 0, 0), (255, 160, 0), (255, 255, 255)],
+            ColorMap::Viridis => &[
+                (68, 1, 84),
+                (59, 82, 139),
+                (33, 145, 140),
+                (94, 201, 98),
+                (253, 231, 37),
+            ],
+        }
+    }
+
+    /// The colour of the map at the position `t` in [0,1]
+    pub fn color(&self, t: f32) -> RGBA8 {
+        let stops = self.stops();
+        let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (t as usize).min(stops.len() - 2);
+        let fraction = t - index as f32;
+
+        let (r0, g0, b0) = stops[index];
+        let (r1, g1, b1) = stops[index + 1];
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * fraction) as u8;
+
+        RGBA8::new(mix(r0, r1), mix(g0, g1), mix(b0, b1), 255)
+    }
+}
+
+/// The different events that can be called to update states in the spectrogram
+#[derive(Debug, Clone)]
+pub enum SpectrogramEvents {
+    /// Add new spectra to the end of the spectrogram
+    Push(Vec<Vec<f32>>),
+    /// Change the colour map
+    ChangeColorMap(ColorMap),
+    /// Change the scale that is used to map the levels onto the colour map
+    ChangeMeterScale(MeterScale),
+    /// Change the range of levels that is mapped onto the colour map in dB
+    ChangeRange(f32, f32),
+}
+
+/// A scrolling spectrogram showing successive spectra as columns of colour.
+///
+/// As an input it requires a lens to the [`SpectrumFrames`] produced by a `SpectrumAnalyser`.
+/// The levels are mapped through a [`MeterScale`] in the same way a `Meter` maps its input,
+/// so a spectrogram and a meter using the same scale and range show the same level at the same position.
+/// The frequency axis is logarithmic, from 20 Hz at the bottom up to the nyquist frequency.
+///
+/// Each spectrum is rendered into a single column of a femtovg image,
+/// which is uploaded once per draw and scrolled by offsetting the image pattern.
+///
+/// Example:
+/// ```rust
+/// Spectrogram::new(cx, Data::spectrum, 48000.0, 2048)
+/// .color_map(ColorMap::Viridis)
+/// .range(-100.0, 0.0);
+/// ```
+#[derive(Lens)]
+pub struct Spectrogram {
+    /// The first and last bin that are combined into every row, from the bottom up
+    rows: Vec<(usize, usize)>,
+    color_map: ColorMap,
+    scale: MeterScale,
+    /// The lowest level that is shown in dB
+    min: f32,
+    /// The highest level that is shown in dB
+    max: f32,
+    /// The column the next spectrum is written to
+    next_column: usize,
+    /// The level of every row per column from the top down, empty for columns without a spectrum.
+    /// These are kept so a change of the colours can redraw the whole image.
+    levels: Vec<Vec<f32>>,
+    /// The columns that still have to be uploaded to the image
+    /// (column, pixels from the top down)
+    pending: RefCell<Vec<(usize, Vec<RGBA8>)>>,
+    /// The image is created lazily, since there is no canvas available before drawing.
+    /// It has a fixed size independent of the bounds, so it is kept when the view is resized.
+    image: Cell<Option<ImageId>>,
+}
+
+impl Spectrogram {
+    pub fn new<L: Lens<Target = SpectrumFrames>>(
+        cx: &mut Context,
+        lens: L,
+        sample_rate: f32,
+        fft_size: usize,
+    ) -> Handle<Self> {
+        let nyquist = sample_rate / 2.0;
+        let bins = fft_size / 2 + 1;
+        let bin_of = |row: usize| {
+            let f = MIN_FREQUENCY * (nyquist / MIN_FREQUENCY).powf(row as f32 / ROWS as f32);
+            ((f / sample_rate * fft_size as f32) as usize).min(bins - 1)
+        };
+
+        let rows = (0..ROWS)
+            .map(|row| {
+                let first = bin_of(row);
+                (first, bin_of(row + 1).max(first + 1).min(bins))
+            })
+            .collect();
+
+        Self {
+            rows,
+            color_map: ColorMap::Heat,
+            scale: MeterScale::Logarithmic,
+            min: -100.0,
+            max: 0.0,
+            next_column: 0,
+            levels: vec![Vec::new(); COLUMNS],
+            pending: RefCell::new(Vec::new()),
+            image: Cell::new(None),
+        }
+        .build(cx, move |cx| {
+            Binding::new(cx, lens, |cx, value| {
+                cx.emit(SpectrogramEvents::Push(value.get(cx).frames.clone()));
+            });
+        })
+    }
+
+    /// The level of every row of a spectrum from the top down
+    fn row_levels(&self, spectrum: &[f32]) -> Vec<f32> {
+        self.rows
+            .iter()
+            .rev()
+            .map(|(first, last)| {
+                spectrum[*first..*last]
+                    .iter()
+                    .copied()
+                    .fold(f32::NEG_INFINITY, f32::max)
+            })
+            .collect()
+    }
+
+    /// Turn the levels of a column into pixels
+    fn pixels(&self, levels: &[f32]) -> Vec<RGBA8> {
+        let min = self.scale.map_db(self.min);
+        let max = self.scale.map_db(self.max);
+
+        levels
+            .iter()
+            .map(|level| {
+                self.color_map
+                    .color((self.scale.map_db(level.min(self.max)) - min) / (max - min))
+            })
+            .collect()
+    }
+
+    /// Redraw every column that holds a spectrum, after the colours changed
+    fn recolor(&self) {
+        let mut pending = self.pending.borrow_mut();
+        pending.clear();
+        for (column, levels) in self.levels.iter().enumerate() {
+            if !levels.is_empty() {
+                pending.push((column, self.pixels(levels)));
+            }
+        }
+    }
+}
+
+impl Drop for Spectrogram {
+    fn drop(&mut self) {
+        if let (Some(image), Ok(mut released)) = (self.image.get(), RELEASED_IMAGES.lock()) {
+            released.push(image);
+        }
+    }
+}
+
+impl View for Spectrogram {
+    fn element(&self) -> Option<String> {
+        Some("spectrogram".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|spectrogram_event, _| match spectrogram_event {
+            SpectrogramEvents::Push(frames) => {
+                let mut pending = self.pending.borrow_mut();
+
+                for spectrum in frames {
+                    let levels = self.row_levels(spectrum);
+                    pending.push((self.next_column, self.pixels(&levels)));
+                    self.levels[self.next_column] = levels;
+                    self.next_column = (self.next_column + 1) % COLUMNS;
+                }
+
+                // Older columns would be overwritten anyway if the view isn't drawn for a while
+                let excess = pending.len().saturating_sub(COLUMNS);
+                pending.drain(..excess);
+
+                cx.style.needs_redraw = true;
+            }
+            SpectrogramEvents::ChangeColorMap(color_map) => {
+                self.color_map = *color_map;
+                self.recolor();
+                cx.style.needs_redraw = true;
+            }
+            SpectrogramEvents::ChangeMeterScale(scale) => {
+                self.scale = *scale;
+                self.recolor();
+                cx.style.needs_redraw = true;
+            }
+            SpectrogramEvents::ChangeRange(min, max) => {
+                self.min = *min;
+                self.max = *max;
+                self.recolor();
+                cx.style.needs_redraw = true;
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
+        let entity = cx.current();
+
+        let bounds = cx.cache().get_bounds(entity);
+
+        //Skip spectrograms with no width or no height
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        if let Ok(mut released) = RELEASED_IMAGES.lock() {
+            for image in released.drain(..) {
+                canvas.delete_image(image);
+            }
+        }
+
+        let image = match self.image.get() {
+            Some(image) => image,
+            None => match canvas.create_image_empty(
+                COLUMNS,
+                ROWS,
+                PixelFormat::Rgba8,
+                ImageFlags::REPEAT_X | ImageFlags::NEAREST,
+            ) {
+                Ok(image) => {
+                    self.image.set(Some(image));
+                    image
+                }
+                Err(_) => return,
+            },
+        };
+
+        for (column, pixels) in self.pending.borrow_mut().drain(..) {
+            let _ = canvas.update_image(image, ImgRef::new(&pixels, 1, ROWS), column, 0);
+        }
+
+        let opacity = cx.cache().get_opacity(entity);
+
+        // The pattern repeats horizontally, so shifting it to the left by the next column
+        // puts the oldest column at the left edge and the newest at the right edge
+        let offset = self.next_column as f32 / COLUMNS as f32 * bounds.w;
+        let paint = Paint::image(
+            image,
+            bounds.x - offset,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            0.0,
+            opacity,
+        );
+
+        let mut path = Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, paint);
+    }
+}
+
+pub trait SpectrogramHandle {
+    fn color_map(self, val: impl Res<ColorMap>) -> Self;
+    fn scale(self, val: impl Res<MeterScale>) -> Self;
+    fn range(self, min: f32, max: f32) -> Self;
+}
+
+impl SpectrogramHandle for Handle<'_, Spectrogram> {
+    fn color_map(self, val: impl Res<ColorMap>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, SpectrogramEvents::ChangeColorMap(value));
+        });
+
+        self
+    }
+
+    fn scale(self, val: impl Res<MeterScale>) -> Self {
+        val.set_or_bind(self.cx, self.entity, |cx, entity, value| {
+            entity.emit(cx, SpectrogramEvents::ChangeMeterScale(value));
+        });
+
+        self
+    }
+
+    fn range(self, min: f32, max: f32) -> Self {
+        self.entity
+            .emit(self.cx, SpectrogramEvents::ChangeRange(min, max));
+
+        self
+    }
+}
@@ -0,0 +1,133 @@
+use crate::lin2db;
+use rtrb::{Consumer, Producer, RingBuffer};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use vizia::*;
+
+/// The amount of samples that can be queued before the audio thread starts dropping them
+const TAP_CAPACITY: usize = 1 << 16;
+
+/// Create the queue that carries raw samples from the audio thread to the spectrum analyser
+pub fn tap() -> (Producer<f32>, Consumer<f32>) {
+    RingBuffer::new(TAP_CAPACITY)
+}
+
+/// A batch of spectra that were calculated since the previous batch.
+///
+/// The sequence number makes sure two batches with the same content are still seen as a change.
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct SpectrumFrames {
+    pub sequence: u64,
+    /// The magnitude of every bin in dBFS, one `Vec` per spectrum
+    pub frames: Vec<Vec<f32>>,
+}
+
+/// Calculates spectra of overlapping, Hann windowed blocks of samples.
+///
+/// This is not real-time safe and runs outside of the audio thread.
+pub struct SpectrumAnalyser {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// The gain that brings a full scale sine to 0 dBFS
+    normalisation: f32,
+    /// The amount of samples between the start of two spectra
+    hop: usize,
+    /// The samples that have not been fully used yet
+    input: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyser {
+    pub fn new(size: usize, hop: usize) -> Self {
+        let window: Vec<f32> = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        let normalisation = 2.0 / window.iter().sum::<f32>();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(size),
+            window,
+            normalisation,
+            hop,
+            input: Vec::with_capacity(size * 2),
+            buffer: vec![Complex::new(0.0, 0.0); size],
+        }
+    }
+
+    /// The amount of samples per spectrum
+    pub fn size(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Add samples and return every spectrum that could be calculated with them
+    pub fn push(&mut self, samples: impl IntoIterator<Item = f32>) -> Vec<Vec<f32>> {
+        self.input.extend(samples);
+
+        let size = self.size();
+        let mut frames = Vec::new();
+
+        while self.input.len() >= size {
+            for ((bin, sample), window) in self.buffer.iter_mut().zip(&self.input).zip(&self.window)
+            {
+                *bin = Complex::new(sample * window, 0.0);
+            }
+
+            self.fft.process(&mut self.buffer);
+
+            frames.push(
+                self.buffer[..size / 2 + 1]
+                    .iter()
+                    .map(|bin| lin2db(bin.norm() * self.normalisation))
+                    .collect(),
+            );
+
+            self.input.drain(..self.hop);
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 1024;
+
+    fn sine(amplitude: f32, bin: f32, length: usize) -> impl Iterator<Item = f32> {
+        (0..length).map(move |i| amplitude * (2.0 * PI * bin * i as f32 / SIZE as f32).sin())
+    }
+
+    #[test]
+    fn full_scale_sine_reads_0_dbfs() {
+        let mut analyser = SpectrumAnalyser::new(SIZE, SIZE);
+        let frames = analyser.push(sine(1.0, 64.0, SIZE));
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), SIZE / 2 + 1);
+        assert!(frames[0][64].abs() < 0.01, "{} dB", frames[0][64]);
+    }
+
+    #[test]
+    fn levels_follow_the_amplitude() {
+        let mut analyser = SpectrumAnalyser::new(SIZE, SIZE);
+        let frames = analyser.push(sine(0.1, 100.0, SIZE));
+
+        assert!(
+            (frames[0][100] + 20.0).abs() < 0.01,
+            "{} dB",
+            frames[0][100]
+        );
+        // The Hann window keeps the leakage far from the sine low
+        assert!(frames[0][200] < -100.0);
+    }
+
+    #[test]
+    fn spectra_overlap_by_the_hop() {
+        let mut analyser = SpectrumAnalyser::new(SIZE, SIZE / 2);
+        assert!(analyser.push(sine(1.0, 64.0, SIZE - 1)).is_empty());
+        assert_eq!(analyser.push(sine(1.0, 64.0, SIZE)).len(), 2);
+    }
+}
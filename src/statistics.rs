@@ -0,0 +1,497 @@
+use crate::measurement::MeasurementFrame;
+use crate::{create_timestamped, lin2db};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use vizia::vg::{Color, Paint};
+use vizia::*;
+
+/// The lowest level the histograms collect in dB. Everything below ends up in the first bin.
+const HISTOGRAM_MIN: f32 = -90.0;
+/// The highest level the histograms collect in dB. Everything above ends up in the last bin.
+const HISTOGRAM_MAX: f32 = 0.0;
+/// The width of a histogram bin in dB
+const BIN_WIDTH: f32 = 0.5;
+
+/// The time spent at each level, in bins of half a dB
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct Histogram {
+    /// The seconds spent in each bin
+    seconds: Vec<f64>,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            seconds: vec![0.0; ((HISTOGRAM_MAX - HISTOGRAM_MIN) / BIN_WIDTH) as usize],
+        }
+    }
+
+    /// Add the duration in seconds to the bin of the level in dB.
+    /// Levels that aren't finite, like digital silence or a short-term loudness
+    /// before the first 3s, have no bin and aren't counted.
+    pub fn add(&mut self, level: f32, duration: f64) {
+        if !level.is_finite() {
+            return;
+        }
+
+        let index = ((level - HISTOGRAM_MIN) / BIN_WIDTH) as usize;
+        let index = index.min(self.seconds.len() - 1);
+        self.seconds[index] += duration;
+    }
+
+    /// The total amount of seconds collected
+    pub fn total(&self) -> f64 {
+        self.seconds.iter().sum()
+    }
+
+    /// The level in dB below which the given percentage of the time was spent.
+    /// Returns `None` if nothing was collected yet.
+    pub fn percentile(&self, percent: f32) -> Option<f32> {
+        let total = self.total();
+        if total == 0.0 {
+            return None;
+        }
+
+        let target = total * (percent as f64 / 100.0);
+        let mut sum = 0.0;
+        for (level, seconds) in self.bins() {
+            sum += seconds;
+            if sum >= target {
+                return Some(level + BIN_WIDTH);
+            }
+        }
+
+        Some(HISTOGRAM_MAX)
+    }
+
+    /// The lower edge of every bin in dB and the seconds spent in it
+    pub fn bins(&self) -> impl Iterator<Item = (f32, f64)> + '_ {
+        self.seconds
+            .iter()
+            .enumerate()
+            .map(|(i, seconds)| (HISTOGRAM_MIN + i as f32 * BIN_WIDTH, *seconds))
+    }
+}
+
+/// The level statistics of a single channel over a session.
+///
+/// The statistics are collected from the measurement frames of the audio thread,
+/// so every process cycle counts with its real duration.
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct SessionStatistics {
+    /// The histogram of the peak levels of every process cycle
+    pub peak: Histogram,
+    /// The histogram of the RMS levels of every process cycle
+    pub rms: Histogram,
+    /// The histogram of the short-term loudness
+    pub short_term: Histogram,
+    /// The highest sample value
+    pub max_peak: f32,
+    /// The lowest RMS of a process cycle in dBFS, ignoring digital silence
+    pub min_rms: f32,
+    /// The highest RMS of a process cycle in dBFS
+    pub max_rms: f32,
+    /// The sum of squares of all samples
+    sum_squares: f64,
+    /// The amount of samples collected
+    samples: u64,
+    /// The length of the session in seconds
+    pub duration: f64,
+    /// The RMS level in dBFS above which time is counted in `time_above`
+    pub threshold: f32,
+    /// The seconds the RMS of a process cycle spent above the threshold
+    pub time_above: f64,
+}
+
+impl SessionStatistics {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            peak: Histogram::new(),
+            rms: Histogram::new(),
+            short_term: Histogram::new(),
+            max_peak: 0.0,
+            min_rms: f32::INFINITY,
+            max_rms: f32::NEG_INFINITY,
+            sum_squares: 0.0,
+            samples: 0,
+            duration: 0.0,
+            threshold,
+            time_above: 0.0,
+        }
+    }
+
+    /// Add the measurements of a channel
+    pub fn add_frames(&mut self, frames: &[MeasurementFrame], channel: usize, sample_rate: f32) {
+        for frame in frames.iter().filter(|frame| channel < frame.channel_count) {
+            let measurement = &frame.channels[channel];
+            let duration = frame.length as f64 / sample_rate as f64;
+            let rms = lin2db(measurement.rms);
+
+            self.peak.add(lin2db(measurement.peak), duration);
+            self.rms.add(rms, duration);
+            self.short_term.add(measurement.short_term, duration);
+
+            self.max_peak = self.max_peak.max(measurement.peak);
+            if measurement.rms > 0.0 {
+                self.min_rms = self.min_rms.min(rms);
+            }
+            self.max_rms = self.max_rms.max(rms);
+
+            self.sum_squares += (measurement.rms as f64).powi(2) * frame.length as f64;
+            self.samples += frame.length as u64;
+            self.duration += duration;
+
+            if rms > self.threshold {
+                self.time_above += duration;
+            }
+        }
+    }
+
+    /// The RMS of the whole session in dBFS
+    pub fn session_rms(&self) -> f32 {
+        lin2db((self.sum_squares / self.samples.max(1) as f64).sqrt() as f32)
+    }
+
+    /// The ratio between the highest peak and the RMS of the whole session in dB
+    pub fn crest_factor(&self) -> f32 {
+        lin2db(self.max_peak) - self.session_rms()
+    }
+
+    /// Start a new session, keeping the threshold
+    pub fn reset(&mut self) {
+        *self = Self::new(self.threshold);
+    }
+
+    /// Write the statistics and histograms to a new CSV file, named after the path and
+    /// the current time so an earlier export is kept. Returns the path that was written.
+    ///
+    /// Levels that weren't measured, like the RMS range of a session without any signal,
+    /// are left empty.
+    pub fn export(&self, path: &Path) -> io::Result<PathBuf> {
+        let (mut file, path) = create_timestamped(path)?;
+
+        let percentile = |histogram: &Histogram, percent: f32| {
+            histogram
+                .percentile(percent)
+                .map(|level| format!("{:.1}", level))
+                .unwrap_or_default()
+        };
+
+        writeln!(file, "statistic,value")?;
+        writeln!(file, "duration_s,{:.1}", self.duration)?;
+        writeln!(file, "max_peak_dbfs,{}", level(lin2db(self.max_peak), ""))?;
+        writeln!(file, "min_rms_dbfs,{}", level(self.min_rms, ""))?;
+        writeln!(file, "max_rms_dbfs,{}", level(self.max_rms, ""))?;
+        writeln!(file, "session_rms_dbfs,{}", level(self.session_rms(), ""))?;
+        writeln!(file, "crest_factor_db,{}", level(self.crest_factor(), ""))?;
+        writeln!(file, "threshold_dbfs,{:.1}", self.threshold)?;
+        writeln!(file, "time_above_threshold_s,{:.1}", self.time_above)?;
+        for percent in [10.0, 50.0, 95.0] {
+            writeln!(file, "rms_p{},{}", percent, percentile(&self.rms, percent))?;
+            writeln!(
+                file,
+                "short_term_p{},{}",
+                percent,
+                percentile(&self.short_term, percent)
+            )?;
+        }
+
+        writeln!(file)?;
+        writeln!(file, "level_db,peak_s,rms_s,short_term_s")?;
+        for (((level, peak), (_, rms)), (_, short_term)) in self
+            .peak
+            .bins()
+            .zip(self.rms.bins())
+            .zip(self.short_term.bins())
+        {
+            writeln!(
+                file,
+                "{:.1},{:.3},{:.3},{:.3}",
+                level, peak, rms, short_term
+            )?;
+        }
+
+        Ok(path)
+    }
+}
+
+/// A level with one decimal, or `missing` if there is none
+fn level(value: f32, missing: &str) -> String {
+    if value.is_finite() {
+        format!("{:.1}", value)
+    } else {
+        missing.to_string()
+    }
+}
+
+/// The events the statistics panel sends up to the model that owns the [`SessionStatistics`]
+#[derive(Debug, Clone)]
+pub enum StatisticsEvents {
+    /// Clear all statistics and start a new session
+    Reset,
+    /// Write the statistics to a CSV file named after the path and the current time
+    Export(PathBuf),
+    /// Change the level above which time is counted
+    ChangeThreshold(f32),
+}
+
+/// A panel showing the statistics of a session and the histogram of the levels.
+///
+/// As an input it requires a lens to the [`SessionStatistics`].
+/// The panel doesn't own the statistics. Its reset and export buttons emit [`StatisticsEvents`],
+/// which have to be handled by the model the lens points into.
+///
+/// Example:
+/// ```rust
+/// StatisticsPanel::new(cx, Data::statistics, "statistics.csv");
+/// ```
+pub struct StatisticsPanel {}
+
+impl StatisticsPanel {
+    pub fn new<L: Lens<Target = SessionStatistics>>(
+        cx: &mut Context,
+        lens: L,
+        export_path: impl Into<PathBuf>,
+    ) -> Handle<Self> {
+        let export_path = export_path.into();
+
+        Self {}.build(cx, move |cx| {
+            HStack::new(cx, move |cx| {
+                HistogramGraph::new(cx, lens.clone());
+
+                VStack::new(cx, move |cx| {
+                    Label::new(
+                        cx,
+                        lens.clone()
+                            .map(|s| format!("Max peak: {} dBFS", level(lin2db(s.max_peak), "-"))),
+                    );
+                    Label::new(
+                        cx,
+                        lens.clone().map(|s| {
+                            format!(
+                                "RMS min/max: {} / {} dBFS",
+                                level(s.min_rms, "-"),
+                                level(s.max_rms, "-")
+                            )
+                        }),
+                    );
+                    Label::new(
+                        cx,
+                        lens.clone()
+                            .map(|s| format!("Crest factor: {} dB", level(s.crest_factor(), "-"))),
+                    );
+                    Label::new(
+                        cx,
+                        lens.clone().map(|s| {
+                            let percentile = |percent| {
+                                s.short_term
+                                    .percentile(percent)
+                                    .map(|level| format!("{:.1}", level))
+                                    .unwrap_or_else(|| String::from("-"))
+                            };
+                            format!(
+                                "Short-term P10/P50/P95: {} / {} / {} LUFS",
+                                percentile(10.0),
+                                percentile(50.0),
+                                percentile(95.0)
+                            )
+                        }),
+                    );
+                    Label::new(
+                        cx,
+                        lens.clone().map(|s| {
+                            format!(
+                                "Above {:.1} dBFS: {:.1} s of {:.1} s",
+                                s.threshold, s.time_above, s.duration
+                            )
+                        }),
+                    );
+
+                    HStack::new(cx, move |cx| {
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(StatisticsEvents::Reset),
+                            |cx| Label::new(cx, "Reset"),
+                        );
+                        Button::new(
+                            cx,
+                            move |cx| cx.emit(StatisticsEvents::Export(export_path.clone())),
+                            |cx| Label::new(cx, "Export"),
+                        );
+                    })
+                    .class("statistics_buttons");
+                });
+            });
+        })
+    }
+}
+
+impl View for StatisticsPanel {
+    fn element(&self) -> Option<String> {
+        Some("statistics".to_string())
+    }
+}
+
+/// The histograms of the peak, RMS and short-term loudness drawn on top of each other
+pub struct HistogramGraph {
+    statistics: Option<SessionStatistics>,
+}
+
+impl HistogramGraph {
+    pub fn new<L: Lens<Target = SessionStatistics>>(cx: &mut Context, lens: L) -> Handle<Self> {
+        Self { statistics: None }.build(cx, move |cx| {
+            Binding::new(cx, lens, |cx, value| {
+                cx.emit(HistogramGraphEvents::Update(value.get(cx).clone()));
+            });
+        })
+    }
+}
+
+enum HistogramGraphEvents {
+    Update(SessionStatistics),
+}
+
+impl View for HistogramGraph {
+    fn element(&self) -> Option<String> {
+        Some("histogram".to_string())
+    }
+
+    fn event(&mut self, cx: &mut Context, event: &mut Event) {
+        event.map(|histogram_event, _| match histogram_event {
+            HistogramGraphEvents::Update(statistics) => {
+                self.statistics = Some(statistics.clone());
+                cx.style.needs_redraw = true;
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
+        let entity = cx.current();
+
+        let bounds = cx.cache().get_bounds(entity);
+
+        //Skip histograms with no width or no height
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let statistics = match &self.statistics {
+            Some(statistics) => statistics,
+            None => return,
+        };
+
+        let opacity = cx.cache().get_opacity(entity);
+
+        let series = [
+            (&statistics.peak, Color::rgb(0, 244, 70)),
+            (&statistics.rms, Color::rgb(244, 220, 0)),
+            (&statistics.short_term, Color::rgb(80, 160, 255)),
+        ];
+
+        for (histogram, mut color) in series {
+            let highest = histogram
+                .bins()
+                .map(|(_, seconds)| seconds)
+                .fold(0.0, f64::max);
+            if highest == 0.0 {
+                continue;
+            }
+
+            let bins = histogram.seconds.len() as f32;
+            let bin_width = bounds.w / bins;
+
+            color.set_alphaf(0.5 * opacity);
+
+            let mut path = vizia::vg::Path::new();
+            for (i, (_, seconds)) in histogram.bins().enumerate() {
+                let height = (seconds / highest) as f32 * bounds.h;
+                path.rect(
+                    bounds.x + i as f32 * bin_width,
+                    bounds.y + bounds.h - height,
+                    bin_width,
+                    height,
+                );
+            }
+
+            canvas.fill_path(&mut path, Paint::color(color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn percentiles_are_the_upper_bin_edges() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        histogram.add(-30.2, 9.0);
+        histogram.add(-10.2, 1.0);
+        assert_eq!(histogram.percentile(10.0), Some(-30.0));
+        assert_eq!(histogram.percentile(90.0), Some(-30.0));
+        assert_eq!(histogram.percentile(95.0), Some(-10.0));
+    }
+
+    #[test]
+    fn levels_outside_the_histogram_are_clamped_and_missing_ones_skipped() {
+        let mut histogram = Histogram::new();
+        histogram.add(-200.0, 1.0);
+        histogram.add(6.0, 1.0);
+        histogram.add(f32::NEG_INFINITY, 1.0);
+        histogram.add(f32::NAN, 1.0);
+
+        assert_eq!(histogram.total(), 2.0);
+        assert_eq!(histogram.percentile(50.0), Some(HISTOGRAM_MIN + BIN_WIDTH));
+        assert_eq!(histogram.percentile(100.0), Some(HISTOGRAM_MAX));
+    }
+
+    #[test]
+    fn frames_add_their_duration() {
+        let mut silent = MeasurementFrame::new(0, 24000, 1);
+        silent.channels[0].short_term = f32::NEG_INFINITY;
+        let mut loud = MeasurementFrame::new(24000, 48000, 1);
+        loud.channels[0].peak = 1.0;
+        loud.channels[0].rms = 0.1;
+        loud.channels[0].short_term = -20.0;
+
+        let mut statistics = SessionStatistics::new(-30.0);
+        statistics.add_frames(&[silent, loud], 0, 48000.0);
+
+        assert_eq!(statistics.duration, 1.5);
+        assert_eq!(statistics.time_above, 1.0);
+        assert_eq!(statistics.max_peak, 1.0);
+        assert_eq!(statistics.min_rms, -20.0);
+        // The silence before the first short-term loudness doesn't count
+        assert_eq!(statistics.short_term.total(), 1.0);
+        assert_eq!(statistics.short_term.percentile(10.0), Some(-19.5));
+
+        // Channels the frames don't have are ignored
+        let mut other = SessionStatistics::new(-30.0);
+        other.add_frames(&[loud], 1, 48000.0);
+        assert_eq!(other.duration, 0.0);
+    }
+
+    #[test]
+    fn exports_leave_missing_levels_empty() {
+        let directory = std::env::temp_dir().join(format!("statistics_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let statistics = SessionStatistics::new(-20.0);
+        let first = statistics
+            .export(&directory.join("statistics.csv"))
+            .unwrap();
+        let second = statistics
+            .export(&directory.join("statistics.csv"))
+            .unwrap();
+        let text = fs::read_to_string(&first).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_ne!(first, second);
+        assert!(text.contains("\nmin_rms_dbfs,\n"));
+        assert!(text.contains("\nmax_peak_dbfs,\n"));
+        assert!(!text.contains("inf"));
+    }
+}
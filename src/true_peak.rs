@@ -0,0 +1,114 @@
+use std::f64::consts::PI;
+
+/// The oversampling factor. ITU-R BS.1770 requires at least 4x for sample rates up to 48 kHz.
+const OVERSAMPLING: usize = 4;
+/// The amount of taps of every polyphase branch of the interpolation filter
+const TAPS_PER_PHASE: usize = 12;
+
+/// A true peak detector following ITU-R BS.1770 Annex 2.
+///
+/// The signal is oversampled four times with a windowed sinc interpolation filter,
+/// and the highest absolute value of the interpolated signal is reported.
+/// The filter history is a fixed array, so processing is real-time safe.
+pub struct TruePeak {
+    /// The interpolation filter split into its polyphase branches
+    phases: [[f32; TAPS_PER_PHASE]; OVERSAMPLING],
+    /// The most recent input samples, newest first
+    history: [f32; TAPS_PER_PHASE],
+}
+
+impl TruePeak {
+    pub fn new() -> Self {
+        let taps = OVERSAMPLING * TAPS_PER_PHASE;
+        let centre = (taps - 1) as f64 / 2.0;
+
+        let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+        for n in 0..taps {
+            let t = (n as f64 - centre) / OVERSAMPLING as f64;
+            let sinc = if t == 0.0 {
+                1.0
+            } else {
+                (PI * t).sin() / (PI * t)
+            };
+            // Blackman window to keep the ripple of the short filter low
+            let x = n as f64 / (taps - 1) as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos();
+
+            phases[n % OVERSAMPLING][n / OVERSAMPLING] = (sinc * window) as f32;
+        }
+
+        Self {
+            phases,
+            history: [0.0; TAPS_PER_PHASE],
+        }
+    }
+
+    /// Run a block of samples through the detector and return the true peak of the block
+    pub fn process(&mut self, input: &[f32]) -> f32 {
+        let mut peak = 0.0f32;
+
+        for sample in input {
+            self.history.copy_within(0..TAPS_PER_PHASE - 1, 1);
+            self.history[0] = *sample;
+
+            for phase in &self.phases {
+                let value: f32 = phase
+                    .iter()
+                    .zip(&self.history)
+                    .map(|(tap, sample)| tap * sample)
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+
+        // The interpolation can never show less than the samples themselves
+        input
+            .iter()
+            .fold(peak, |peak, sample| peak.max(sample.abs()))
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS_PER_PHASE];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sine at a quarter of the sample rate with the phase in radians
+    fn quarter_rate_sine(phase: f64, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|n| (PI / 2.0 * n as f64 + phase).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_peak_between_the_samples() {
+        // Shifted by 45 degrees every sample lands at +-0.707, 3 dB below the real peak
+        let input = quarter_rate_sine(PI / 4.0, 480);
+        let sample_peak = input
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((sample_peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001);
+
+        let mut true_peak = TruePeak::new();
+        let peak = true_peak.process(&input);
+        assert!((peak - 1.0).abs() < 0.02, "{}", peak);
+    }
+
+    #[test]
+    fn peaks_on_the_samples_stay_the_same() {
+        let input = quarter_rate_sine(0.0, 480);
+        let peak = TruePeak::new().process(&input);
+        assert!((peak - 1.0).abs() < 0.02, "{}", peak);
+    }
+
+    #[test]
+    fn reset_clears_the_history() {
+        let mut true_peak = TruePeak::new();
+        true_peak.process(&quarter_rate_sine(PI / 4.0, 480));
+        true_peak.reset();
+        assert_eq!(true_peak.process(&[0.0; 64]), 0.0);
+    }
+}
@@ -0,0 +1,394 @@
+use crate::config::HttpConfig;
+use crate::measurement::MeasurementFrame;
+use crate::metrics::Metrics;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The GUID every WebSocket handshake appends to the key of the client
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The path the levels are streamed on
+const LEVELS_PATH: &str = "/levels";
+/// The path the Prometheus metrics are served on
+const METRICS_PATH: &str = "/metrics";
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The amount of updates that can wait for a WebSocket client before it is dropped as too slow
+const CLIENT_BACKLOG: usize = 16;
+
+/// The page served at the root, showing a bar per channel
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>jack_meter</title>
+<style>
+body { background: #000; color: #fff; font-family: sans-serif; margin: 0; }
+#meters { display: flex; gap: 8px; height: 90vh; padding: 8px; }
+.channel { flex: 1; display: flex; flex-direction: column; }
+.track { flex: 1; background: #202020; position: relative; }
+.bar { position: absolute; bottom: 0; width: 100%; background: #00f446; }
+.clip .bar { background: #f54e47; }
+.value { text-align: center; padding: 4px; }
+</style>
+</head>
+<body>
+<div id="meters"></div>
+<script>
+const meters = document.getElementById("meters");
+const socket = new WebSocket("ws://" + location.host + "/levels");
+socket.onmessage = (message) => {
+  const frame = JSON.parse(message.data);
+  while (meters.children.length < frame.channels.length) {
+    meters.insertAdjacentHTML("beforeend",
+      '<div class="channel"><div class="track"><div class="bar"></div></div><div class="value"></div></div>');
+  }
+  frame.channels.forEach((channel, index) => {
+    const meter = meters.children[index];
+    const peak = channel.peak === null ? -Infinity : channel.peak;
+    meter.querySelector(".bar").style.height = Math.max(0, Math.min(100, (peak + 60) / 60 * 100)) + "%";
+    meter.querySelector(".value").textContent = peak === -Infinity ? "-inf" : peak.toFixed(1);
+    meter.classList.toggle("clip", channel.clip);
+  });
+};
+</script>
+</body>
+</html>
+"#;
+
+/// The queues of the WebSocket connections the levels are sent to.
+/// Every connection writes on its own thread, so a client that stalls can't hold up the others.
+type Clients = Arc<Mutex<Vec<SyncSender<Arc<[u8]>>>>>;
+
+/// Start the HTTP server and stream the frames from the receiver to its WebSocket clients.
+///
+/// The root serves a page showing the levels, and `/levels` accepts WebSocket connections
+/// that receive one JSON object (see [`MeasurementFrame::to_json`]) per period.
+/// The frames of a period are combined, so a client that sees a clip flag
+/// knows a sample reached full scale somewhere in that period.
+/// Every frame also updates the [`Metrics`] served at `/metrics`.
+pub fn spawn_server(
+    config: &HttpConfig,
+    sample_rate: f32,
+    silence_threshold: f32,
+    frames: Receiver<MeasurementFrame>,
+) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(&config.address)?;
+    let clients = Clients::default();
+    let metrics = Arc::new(Mutex::new(Metrics::new(sample_rate, silence_threshold)));
+
+    let accepted = clients.clone();
+    let scraped = metrics.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let clients = accepted.clone();
+            let metrics = scraped.clone();
+            // Every request is handled on its own thread, so a slow client can't block the others
+            thread::spawn(move || {
+                if let Err(err) = handle_connection(stream, &clients, &metrics) {
+                    eprintln!("Failed to handle an HTTP request: {}", err);
+                }
+            });
+        }
+    });
+
+    let period = Duration::from_secs_f32(1.0 / config.rate);
+    Ok(thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut next_send = Instant::now() + period;
+
+        loop {
+            match frames.recv_timeout(next_send.saturating_duration_since(Instant::now())) {
+                Ok(frame) => {
+                    if let Ok(mut metrics) = metrics.lock() {
+                        metrics.update(&frame);
+                    }
+                    pending.push(frame);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if now < next_send {
+                continue;
+            }
+            // Skip the updates that were missed instead of sending them in a burst
+            next_send = (next_send + period).max(now);
+
+            if let Some(frame) = MeasurementFrame::combine(&pending) {
+                let message: Arc<[u8]> = text_frame(&frame.to_json()).into();
+                if let Ok(mut clients) = clients.lock() {
+                    // Clients that went away or fell behind are dropped
+                    clients.retain(|client| client.try_send(message.clone()).is_ok());
+                }
+            }
+            pending.clear();
+        }
+    }))
+}
+
+/// Read a request and either answer it or turn the connection into a WebSocket client
+fn handle_connection(
+    stream: TcpStream,
+    clients: &Clients,
+    metrics: &Mutex<Metrics>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let mut websocket_key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut stream = stream;
+    match (method, path, websocket_key) {
+        ("GET", LEVELS_PATH, Some(key)) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                websocket_accept(&key)
+            )?;
+            // A client that stopped reading times out, or is dropped once its backlog is full
+            stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+            let (sender, messages) = sync_channel(CLIENT_BACKLOG);
+            if let Ok(mut clients) = clients.lock() {
+                clients.push(sender);
+            }
+
+            // Write the levels until the client goes away or is dropped
+            while let Ok(message) = messages.recv() {
+                if stream.write_all(&message).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        ("GET", "/", _) => write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            PAGE.len(),
+            PAGE
+        ),
+        ("GET", METRICS_PATH, _) => {
+            let text = match metrics.lock() {
+                Ok(metrics) => metrics.render(),
+                Err(_) => String::new(),
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                text.len(),
+                text
+            )
+        }
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    }
+}
+
+/// Wrap a text in an unmasked WebSocket text frame, as a server sends it
+fn text_frame(text: &str) -> Vec<u8> {
+    let payload = text.as_bytes();
+    let mut frame = vec![0x81];
+
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// The accept key of the handshake, the base64 encoded SHA-1 of the key of the client and the GUID
+fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// SHA-1 as specified in RFC 3174.
+/// It is only used for the WebSocket handshake, where it isn't about security.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, state) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn accepts_the_handshake_of_rfc_6455() {
+        assert_eq!(
+            websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn text_frames_use_the_shortest_length_encoding() {
+        let frame = text_frame(&"a".repeat(125));
+        assert_eq!(frame[..2], [0x81, 125]);
+        assert_eq!(frame.len(), 2 + 125);
+
+        let frame = text_frame(&"a".repeat(126));
+        assert_eq!(frame[..4], [0x81, 126, 0, 126]);
+        assert_eq!(frame.len(), 4 + 126);
+
+        let frame = text_frame(&"a".repeat(65536));
+        assert_eq!(frame[..10], [0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(frame.len(), 10 + 65536);
+    }
+
+    #[test]
+    fn streams_to_a_local_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let clients = Clients::default();
+        let metrics = Arc::new(Mutex::new(Metrics::new(48000.0, -60.0)));
+        let connection_clients = clients.clone();
+        thread::spawn(move || handle_connection(stream, &connection_clients, &metrics));
+
+        write!(
+            client,
+            "GET /levels HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+        )
+        .unwrap();
+
+        let mut response = Vec::new();
+        let mut byte = [0u8];
+        while !response.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        // The connection registers itself right after the handshake
+        while clients.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let message: Arc<[u8]> = text_frame("{}").into();
+        clients.lock().unwrap()[0].try_send(message).unwrap();
+
+        let mut frame = [0u8; 4];
+        client.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, [0x81, 2, b'{', b'}']);
+    }
+}
@@ -0,0 +1,79 @@
+use vizia::*;
+
+/// Frequency weightings as defined in IEC 61672-1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum Weighting {
+    /// Approximates the ear at low levels, strongly attenuating low frequencies
+    A,
+    /// Approximates the ear at high levels, mostly flat with a gentle roll-off at both ends
+    C,
+    /// No weighting at all
+    Z,
+}
+
+impl Weighting {
+    /// The gain of the weighting at the frequency `f` in dB
+    pub fn gain_db(&self, f: f32) -> f32 {
+        let f2 = f * f;
+
+        match self {
+            Weighting::A => {
+                let r = 12194.0f32.powi(2) * f2 * f2
+                    / ((f2 + 20.6f32.powi(2))
+                        * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt()
+                        * (f2 + 12194.0f32.powi(2)));
+                20.0 * r.log10() + 2.0
+            }
+            Weighting::C => {
+                let r =
+                    12194.0f32.powi(2) * f2 / ((f2 + 20.6f32.powi(2)) * (f2 + 12194.0f32.powi(2)));
+                20.0 * r.log10() + 0.06
+            }
+            Weighting::Z => 0.0,
+        }
+    }
+
+    /// The linear amplitude gain of the weighting at the frequency `f`
+    pub fn gain(&self, f: f32) -> f32 {
+        10.0f32.powf(self.gain_db(f) / 20.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check the gains against the table of IEC 61672-1, which is rounded to 0.1 dB
+    fn assert_table(weighting: Weighting, table: &[(f32, f32)]) {
+        for &(f, expected) in table {
+            let gain = weighting.gain_db(f);
+            assert!((gain - expected).abs() < 0.1, "{} Hz: {} dB", f, gain);
+        }
+    }
+
+    #[test]
+    fn a_weighting_follows_the_iec_table() {
+        assert_table(
+            Weighting::A,
+            &[
+                (100.0, -19.1),
+                (1000.0, 0.0),
+                (4000.0, 1.0),
+                (10000.0, -2.5),
+            ],
+        );
+    }
+
+    #[test]
+    fn c_weighting_follows_the_iec_table() {
+        assert_table(
+            Weighting::C,
+            &[(31.5, -3.0), (100.0, -0.3), (1000.0, 0.0), (10000.0, -4.4)],
+        );
+    }
+
+    #[test]
+    fn z_weighting_is_flat() {
+        assert_table(Weighting::Z, &[(31.5, 0.0), (1000.0, 0.0), (16000.0, 0.0)]);
+    }
+}